@@ -0,0 +1,4 @@
+import random
+
+random.seed(int(input()))
+print(random.randint(0, 2 * 10**9), random.randint(0, 2 * 10**9))
@@ -0,0 +1,7 @@
+#include <cstdio>
+
+int main() {
+  long long a, b;
+  scanf("%lld %lld", &a, &b);
+  printf("%lld\n", a + b);
+}
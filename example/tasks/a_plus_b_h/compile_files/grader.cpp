@@ -1,4 +1,4 @@
-#include "code_0.h"
+#include "a_plus_b.h"
 #include <bits/stdc++.h>
 
 #define long long long
@@ -0,0 +1,297 @@
+use crate::errors::{GraderError, GraderResult};
+use std::{
+    fs,
+    io::{self, Read, Seek},
+    path::{Component, Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarZst,
+}
+
+impl ArchiveKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::str::FromStr for ArchiveKind {
+    type Err = GraderError;
+
+    /// Parse `zip`, `tar` or `tar.zst`.
+    fn from_str(kind: &str) -> GraderResult<Self> {
+        match kind {
+            "zip" => Ok(Self::Zip),
+            "tar" => Ok(Self::Tar),
+            "tar.zst" | "tzst" => Ok(Self::TarZst),
+            _ => Err(GraderError::invalid_archive(format!(
+                "unknown archive type {kind}"
+            ))),
+        }
+    }
+}
+
+/// Bounds on what an archive may unpack to.
+#[derive(Debug, Clone, Copy)]
+pub struct ExtractLimit {
+    pub max_bytes: u64,
+    pub max_entries: u64,
+}
+
+impl Default for ExtractLimit {
+    fn default() -> Self {
+        ExtractLimit {
+            max_bytes: u64::MAX,
+            max_entries: u64::MAX,
+        }
+    }
+}
+
+/// Unpack the regular files and directories of an archive into `destination`.
+/// Entries escaping `destination`, links and special files are rejected.
+pub fn extract<R: Read + Seek>(
+    reader: R,
+    kind: ArchiveKind,
+    destination: &Path,
+    limit: ExtractLimit,
+) -> GraderResult<()> {
+    let mut extractor = Extractor {
+        destination,
+        limit,
+        bytes: 0,
+        entries: 0,
+    };
+    match kind {
+        ArchiveKind::Zip => {
+            let mut archive = zip::ZipArchive::new(reader)?;
+            for index in 0..archive.len() {
+                let mut file = archive.by_index(index)?;
+                let path = file.enclosed_name().map(|path| path.to_path_buf());
+                if file.is_dir() {
+                    extractor.create_dir(path)?;
+                } else if file.is_file() {
+                    let size = file.size();
+                    extractor.write_file(path, size, &mut file)?;
+                } else {
+                    return Err(GraderError::invalid_archive(format!(
+                        "unsupported entry {}",
+                        file.name()
+                    )));
+                }
+            }
+        }
+        ArchiveKind::Tar => extractor.extract_tar(tar::Archive::new(reader))?,
+        ArchiveKind::TarZst => {
+            extractor.extract_tar(tar::Archive::new(zstd::Decoder::new(reader)?))?
+        }
+    }
+    Ok(())
+}
+
+struct Extractor<'a> {
+    destination: &'a Path,
+    limit: ExtractLimit,
+    bytes: u64,
+    entries: u64,
+}
+
+impl Extractor<'_> {
+    fn extract_tar<R: Read>(&mut self, mut archive: tar::Archive<R>) -> GraderResult<()> {
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let path = Some(entry.path()?.into_owned());
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => self.create_dir(path)?,
+                tar::EntryType::Regular | tar::EntryType::Continuous => {
+                    let size = entry.size();
+                    self.write_file(path, size, &mut entry)?
+                }
+                tar::EntryType::XGlobalHeader | tar::EntryType::XHeader => (),
+                _ => {
+                    return Err(GraderError::invalid_archive(format!(
+                        "unsupported entry {path:?}"
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn count_entry(&mut self) -> GraderResult<()> {
+        self.entries += 1;
+        if self.entries > self.limit.max_entries {
+            return Err(GraderError::invalid_archive(format!(
+                "more than {} entries",
+                self.limit.max_entries
+            )));
+        }
+        Ok(())
+    }
+
+    fn create_dir(&mut self, path: Option<PathBuf>) -> GraderResult<()> {
+        self.count_entry()?;
+        fs::create_dir_all(self.destination.join(enclosed_path(path)?))?;
+        Ok(())
+    }
+
+    fn write_file(
+        &mut self,
+        path: Option<PathBuf>,
+        size: u64,
+        reader: &mut impl Read,
+    ) -> GraderResult<()> {
+        self.count_entry()?;
+        let path = enclosed_path(path)?;
+        if path.as_os_str().is_empty() {
+            return Err(GraderError::invalid_archive("file entry without a name"));
+        }
+        let path = self.destination.join(path);
+        let remaining = self.limit.max_bytes.saturating_sub(self.bytes);
+        if size > remaining {
+            return Err(GraderError::invalid_archive(format!(
+                "more than {} bytes",
+                self.limit.max_bytes
+            )));
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::File::create(&path)?;
+        let written = io::copy(&mut reader.take(remaining.saturating_add(1)), &mut file)?;
+        if written > remaining {
+            return Err(GraderError::invalid_archive(format!(
+                "more than {} bytes",
+                self.limit.max_bytes
+            )));
+        }
+        self.bytes += written;
+        Ok(())
+    }
+}
+
+/// Accept only relative paths made of plain components, ignoring `.`.
+fn enclosed_path(path: Option<PathBuf>) -> GraderResult<PathBuf> {
+    let path = path.ok_or(GraderError::invalid_archive("entry without a valid name"))?;
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name),
+            _ => Err(GraderError::invalid_archive(format!(
+                "entry {path:?} is outside of the archive root"
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::TempDir;
+    use dotenv::dotenv;
+    use std::io::{Cursor, Write};
+
+    fn zip_archive(files: &[(&str, &str)]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    #[test]
+    fn should_extract_tar_zst() -> GraderResult<()> {
+        dotenv().ok();
+
+        let tmp_dir = TempDir::new("extract_tar_zst");
+        let mut builder = tar::Builder::new(zstd::Encoder::new(Vec::new(), 0)?);
+        for (name, content) in [("./1.in", "1 2\n"), ("./sub/1.sol", "3\n")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content.as_bytes())?;
+        }
+        let archive = builder.into_inner()?.finish()?;
+
+        extract(
+            Cursor::new(archive),
+            ArchiveKind::TarZst,
+            &tmp_dir.0,
+            ExtractLimit::default(),
+        )?;
+
+        assert_eq!(fs::read_to_string(tmp_dir.0.join("1.in"))?, "1 2\n");
+        assert_eq!(
+            fs::read_to_string(tmp_dir.0.join("sub").join("1.sol"))?,
+            "3\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_extract_zip() -> GraderResult<()> {
+        dotenv().ok();
+
+        let tmp_dir = TempDir::new("extract_zip");
+        let archive = zip_archive(&[("1.in", "1 2\n"), ("sub/1.sol", "3\n")]);
+
+        extract(
+            archive,
+            ArchiveKind::Zip,
+            &tmp_dir.0,
+            ExtractLimit::default(),
+        )?;
+
+        assert_eq!(fs::read_to_string(tmp_dir.0.join("1.in"))?, "1 2\n");
+        assert_eq!(
+            fs::read_to_string(tmp_dir.0.join("sub").join("1.sol"))?,
+            "3\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_zip_over_limit() {
+        dotenv().ok();
+
+        let tmp_dir = TempDir::new("extract_zip_over_limit");
+        let limit = ExtractLimit {
+            max_bytes: 4,
+            max_entries: 10,
+        };
+
+        let result = extract(
+            zip_archive(&[("1.in", "1 2\n"), ("1.sol", "3\n")]),
+            ArchiveKind::Zip,
+            &tmp_dir.0,
+            limit,
+        );
+        assert!(matches!(result, Err(GraderError::InvalidArchive { .. })));
+    }
+
+    #[test]
+    fn should_reject_entry_outside_of_root() {
+        assert!(enclosed_path(Some(PathBuf::from("./a/b.in"))).is_ok());
+        assert!(enclosed_path(Some(PathBuf::from("../b.in"))).is_err());
+        assert!(enclosed_path(Some(PathBuf::from("/etc/passwd"))).is_err());
+        assert!(enclosed_path(None).is_err());
+    }
+}
@@ -0,0 +1,583 @@
+//! Grader configuration, read once from the environment and
+//! `scripts/config.yaml` and shared by every submission.
+//!
+//! `get` loads the configuration on first use and hands out the same snapshot
+//! afterwards. `watch` reloads it whenever `config.yaml` changes, so a
+//! submission keeps the snapshot it started with while new submissions pick
+//! up the new one. A configuration that fails validation is never installed.
+
+use crate::errors::{GraderError, GraderResult};
+use crate::instance::compile::CompileLimit;
+use crate::language::Language;
+use crate::utils::load_yaml;
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    env, fs,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use yaml_rust::Yaml;
+
+const DEFAULT_COMPILE_CACHE_SIZE: u64 = 1024;
+const DEFAULT_LOCALE: &str = "en";
+const DEFAULT_MAX_SOURCE_SIZE: u64 = 256;
+const DEFAULT_MAX_SOURCE_FILES: u64 = 16;
+const DEFAULT_RERUN_MARGIN: f64 = 0.05;
+const DEFAULT_BOX_COUNT: u64 = 1000;
+
+static CONFIG: RwLock<Option<Arc<GraderConfig>>> = RwLock::new(None);
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct GraderConfig {
+    /// Directory holding `tasks` and `scripts`, from `BASE_PATH`.
+    pub base_path: PathBuf,
+    /// Directory for submissions and caches, from `TEMPORARY_PATH`.
+    pub temporary_path: PathBuf,
+    /// The isolate binary, from `ISOLATE_PATH`.
+    pub isolate_path: PathBuf,
+    /// Directory mounted in every box, from `ALTERNATIVE_PATH`.
+    pub alternative_path: PathBuf,
+    pub languages: Vec<Language>,
+    /// Locale of messages for submissions that do not ask for one.
+    pub default_locale: String,
+    /// Default message for each verdict, by locale.
+    pub messages: BTreeMap<String, BTreeMap<String, String>>,
+    /// Size of the compile cache in megabytes, 0 disables it.
+    pub compile_cache_size: u64,
+    /// Limits on what a submission may contain, unless its task sets its own.
+    pub source_limit: SourceLimit,
+    /// When to run a test again because its time is close to the limit.
+    pub rerun: RerunPolicy,
+    /// Isolate boxes this process may use.
+    pub boxes: BoxRange,
+}
+
+/// Isolate boxes `first` to `first + count - 1`, from `BOX_FIRST` and
+/// `BOX_COUNT`. Grader processes sharing a machine need disjoint ranges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoxRange {
+    pub first: u64,
+    pub count: u64,
+}
+
+impl Default for BoxRange {
+    fn default() -> Self {
+        BoxRange {
+            first: 0,
+            count: DEFAULT_BOX_COUNT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SourceLimit {
+    /// Total size of the sources in kilobytes.
+    pub max_size: u64,
+    pub max_files: u64,
+}
+
+impl Default for SourceLimit {
+    fn default() -> Self {
+        SourceLimit {
+            max_size: DEFAULT_MAX_SOURCE_SIZE,
+            max_files: DEFAULT_MAX_SOURCE_FILES,
+        }
+    }
+}
+
+/// Tests whose time is within `margin` of the time limit, as a fraction of the
+/// limit, run `attempts` times in total and are judged on the attempt with the
+/// minimum or median time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RerunPolicy {
+    pub margin: f64,
+    /// Runs of a borderline test including the first one, 1 disables reruns.
+    pub attempts: u64,
+    pub time: RerunTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RerunTime {
+    #[default]
+    Min,
+    /// The lower median for an even number of attempts.
+    Median,
+}
+
+impl Default for RerunPolicy {
+    fn default() -> Self {
+        RerunPolicy {
+            margin: DEFAULT_RERUN_MARGIN,
+            attempts: 1,
+            time: RerunTime::default(),
+        }
+    }
+}
+
+impl RerunPolicy {
+    /// Whether a run that took `time_usage` seconds is close enough to
+    /// `time_limit` to be run again.
+    pub fn is_borderline(&self, time_usage: f64, time_limit: f64) -> bool {
+        self.attempts > 1 && (time_usage - time_limit).abs() <= self.margin * time_limit
+    }
+}
+
+fn env_path(name: &str) -> GraderResult<PathBuf> {
+    env::var(name)
+        .map(PathBuf::from)
+        .map_err(|_| GraderError::invalid_config(format!("{name} is not set")))
+}
+
+fn env_u64(name: &str, default: u64) -> GraderResult<u64> {
+    match env::var(name) {
+        Err(_) => Ok(default),
+        Ok(value) => value.parse().map_err(|_| {
+            GraderError::invalid_config(format!("{name} must be a non-negative integer"))
+        }),
+    }
+}
+
+impl GraderConfig {
+    /// Read the paths from the environment and parse `config.yaml` under
+    /// `BASE_PATH`, without validating the result.
+    pub fn from_env() -> GraderResult<Self> {
+        let mut config = GraderConfig {
+            base_path: env_path("BASE_PATH")?,
+            temporary_path: env_path("TEMPORARY_PATH")?,
+            isolate_path: env_path("ISOLATE_PATH")?,
+            alternative_path: env_path("ALTERNATIVE_PATH")?,
+            boxes: BoxRange {
+                first: env_u64("BOX_FIRST", 0)?,
+                count: env_u64("BOX_COUNT", DEFAULT_BOX_COUNT)?,
+            },
+            ..Default::default()
+        };
+        config.read_file()?;
+        Ok(config)
+    }
+
+    fn read_file(&mut self) -> GraderResult<()> {
+        let yaml = load_yaml(&self.config_path())?;
+
+        let compile_limit = CompileLimit::from_yaml(&yaml["compile"], CompileLimit::default());
+        self.compile_cache_size = match yaml["compile"]["cache_size"].as_i64() {
+            None => DEFAULT_COMPILE_CACHE_SIZE,
+            Some(size) => u64::try_from(size).map_err(|_| {
+                GraderError::invalid_config("compile cache_size must not be negative")
+            })?,
+        };
+        self.languages = yaml["language"]
+            .as_vec()
+            .into_iter()
+            .flatten()
+            .map(|language| Language::from_yaml(language, compile_limit))
+            .collect::<GraderResult<_>>()?;
+        let default_source_limit = SourceLimit::default();
+        let source_limit = |key: &str, default: u64| match yaml["submission"][key].as_i64() {
+            None => Ok(default),
+            Some(limit) => u64::try_from(limit).map_err(|_| {
+                GraderError::invalid_config(format!("submission {key} must not be negative"))
+            }),
+        };
+        self.source_limit = SourceLimit {
+            max_size: source_limit("max_source_size", default_source_limit.max_size)?,
+            max_files: source_limit("max_source_files", default_source_limit.max_files)?,
+        };
+        let default_rerun = RerunPolicy::default();
+        self.rerun = RerunPolicy {
+            margin: yaml["rerun"]["margin"]
+                .as_f64()
+                .unwrap_or(default_rerun.margin),
+            attempts: match yaml["rerun"]["attempts"].as_i64() {
+                None => default_rerun.attempts,
+                Some(attempts) => u64::try_from(attempts).map_err(|_| {
+                    GraderError::invalid_config("rerun attempts must not be negative")
+                })?,
+            },
+            time: match yaml["rerun"]["time"].as_str() {
+                None | Some("min") => RerunTime::Min,
+                Some("median") => RerunTime::Median,
+                Some(time) => {
+                    return Err(GraderError::invalid_config(format!(
+                        "unknown rerun time {time:?}, expected min or median"
+                    )))
+                }
+            },
+        };
+        self.default_locale = yaml["locale"].as_str().unwrap_or(DEFAULT_LOCALE).to_owned();
+        let default_locale = &self.default_locale;
+        self.messages = yaml["message"]
+            .as_hash()
+            .into_iter()
+            .flatten()
+            .filter_map(|(status, message)| {
+                let messages = match message {
+                    // A plain string is the message in the default locale.
+                    Yaml::String(message) => {
+                        BTreeMap::from([(default_locale.clone(), message.clone())])
+                    }
+                    Yaml::Hash(messages) => messages
+                        .iter()
+                        .filter_map(|(locale, message)| {
+                            Some((locale.as_str()?.to_owned(), message.as_str()?.to_owned()))
+                        })
+                        .collect(),
+                    _ => return None,
+                };
+                Some((status.as_str()?.to_owned(), messages))
+            })
+            .collect();
+        Ok(())
+    }
+
+    pub fn config_path(&self) -> PathBuf {
+        self.scripts_path().join("config.yaml")
+    }
+
+    pub fn scripts_path(&self) -> PathBuf {
+        self.base_path.join("scripts")
+    }
+
+    pub fn tasks_path(&self) -> PathBuf {
+        self.base_path.join("tasks")
+    }
+
+    /// Directory holding the working directory of every submission, kept
+    /// apart from the caches and logs in `temporary_path` so a submission id
+    /// can never name one of them.
+    pub fn submissions_path(&self) -> PathBuf {
+        self.temporary_path.join("submissions")
+    }
+
+    pub fn language(&self, id: &str) -> Option<&Language> {
+        self.languages.iter().find(|language| language.id == id)
+    }
+
+    /// The default message for a verdict in `locale`, falling back to its
+    /// language without the region (`th` for `th-TH`) and then to the default
+    /// locale. Empty when none is configured.
+    pub fn message(&self, status: &str, locale: Option<&str>) -> String {
+        let messages = match self.messages.get(status) {
+            Some(messages) => messages,
+            None => return String::new(),
+        };
+        let requested = locale.into_iter().flat_map(|locale| {
+            let language = locale.split(['-', '_'].as_ref()).next();
+            std::iter::once(locale).chain(language)
+        });
+        requested
+            .chain(std::iter::once(self.default_locale.as_str()))
+            .find_map(|locale| messages.get(locale))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Check that the configuration can judge submissions: the directories
+    /// exist and every language has an extension and a way to compile and
+    /// run it.
+    pub fn validate(&self) -> GraderResult<()> {
+        for (name, path) in [
+            ("BASE_PATH", &self.base_path),
+            ("TEMPORARY_PATH", &self.temporary_path),
+        ]
+        .iter()
+        {
+            if !path.is_dir() {
+                return Err(GraderError::invalid_config(format!(
+                    "{name} {path:?} is not a directory"
+                )));
+            }
+        }
+
+        if self.source_limit.max_size.checked_mul(1024).is_none() {
+            return Err(GraderError::invalid_config(
+                "submission max_source_size is too large",
+            ));
+        }
+
+        if self.boxes.count == 0 || self.boxes.first.checked_add(self.boxes.count).is_none() {
+            return Err(GraderError::invalid_config(
+                "BOX_COUNT must be positive and the boxes must fit in a u64",
+            ));
+        }
+
+        if self.compile_cache_size.checked_mul(1024 * 1024).is_none() {
+            return Err(GraderError::invalid_config(
+                "compile cache_size is too large",
+            ));
+        }
+
+        if self.rerun.attempts == 0 || !(0.0..1.0).contains(&self.rerun.margin) {
+            return Err(GraderError::invalid_config(
+                "rerun needs at least one attempt and a margin in [0, 1)",
+            ));
+        }
+
+        for (index, language) in self.languages.iter().enumerate() {
+            if self.languages[..index]
+                .iter()
+                .any(|other| other.id == language.id)
+            {
+                return Err(GraderError::invalid_config(format!(
+                    "language {} is declared twice",
+                    language.id
+                )));
+            }
+            if language.extension.is_empty() {
+                return Err(GraderError::invalid_config(format!(
+                    "language {} has no extension",
+                    language.id
+                )));
+            }
+            let scripts = [
+                ("compile_scripts", language.compile_command.is_some()),
+                ("runner_scripts", language.run_command.is_some()),
+            ];
+            for (directory, declared) in scripts.iter() {
+                let script_path = self.scripts_path().join(directory).join(&language.id);
+                if !declared && !script_path.is_file() {
+                    return Err(GraderError::invalid_config(format!(
+                        "language {} has no command or script at {script_path:?}",
+                        language.id
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that isolate can be found. Kept apart from `validate` since only
+    /// judging needs it.
+    pub fn check_sandbox(&self) -> GraderResult<()> {
+        if !self.isolate_path.is_file() {
+            return Err(GraderError::invalid_config(format!(
+                "isolate not found at {:?}",
+                self.isolate_path
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// The current configuration, loaded and validated on first use.
+pub fn get() -> GraderResult<Arc<GraderConfig>> {
+    if let Some(config) = CONFIG.read().unwrap().as_ref() {
+        return Ok(config.clone());
+    }
+
+    let mut current = CONFIG.write().unwrap();
+    if let Some(config) = current.as_ref() {
+        return Ok(config.clone());
+    }
+    let config = Arc::new(load()?);
+    *current = Some(config.clone());
+    Ok(config)
+}
+
+/// Load the configuration again and install it if it is valid.
+pub fn reload() -> GraderResult<Arc<GraderConfig>> {
+    let config = Arc::new(load()?);
+    *CONFIG.write().unwrap() = Some(config.clone());
+    Ok(config)
+}
+
+fn load() -> GraderResult<GraderConfig> {
+    let config = GraderConfig::from_env()?;
+    config.validate()?;
+    Ok(config)
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Poll `config.yaml` every `interval` and reload the configuration when it
+/// changes. An invalid file is logged and the previous configuration kept.
+pub fn watch(interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut last_modified = get()
+            .ok()
+            .and_then(|config| modified(&config.config_path()));
+        loop {
+            tokio::time::sleep(interval).await;
+            let config_path = match get() {
+                Ok(config) => config.config_path(),
+                Err(e) => {
+                    log::error!("unable to load grader config: {e}");
+                    continue;
+                }
+            };
+            let current_modified = modified(&config_path);
+            if current_modified == last_modified {
+                continue;
+            }
+            last_modified = current_modified;
+            match reload() {
+                Ok(_) => log::info!("reloaded grader config from {config_path:?}"),
+                Err(e) => {
+                    log::error!("keeping previous grader config, {config_path:?} is invalid: {e}")
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::tests::{get_example_dir, TempDir};
+
+    fn example_config() -> GraderResult<GraderConfig> {
+        let mut config = GraderConfig {
+            base_path: get_example_dir(),
+            temporary_path: env::temp_dir(),
+            ..Default::default()
+        };
+        config.read_file()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn should_read_example_config() -> GraderResult<()> {
+        let config = example_config()?;
+        config.validate()?;
+
+        assert_eq!(config.language("cpp").unwrap().extension, "cpp");
+        assert!(config.language("cpp17").unwrap().compile_script().is_some());
+        assert_eq!(config.language("brainfuck"), None);
+        assert_eq!(config.message("Correct", None), "Output is correct");
+        assert_eq!(config.message("Unknown", None), "");
+        assert_eq!(config.compile_cache_size, 1024);
+        assert_eq!(config.source_limit.max_size, 256);
+        assert_eq!(config.source_limit.max_files, 16);
+        Ok(())
+    }
+
+    #[test]
+    fn should_localize_messages() -> GraderResult<()> {
+        let config = example_config()?;
+
+        assert_eq!(config.default_locale, "en");
+        assert_eq!(config.message("Correct", Some("th")), "คำตอบถูกต้อง");
+        assert_eq!(config.message("Correct", Some("th-TH")), "คำตอบถูกต้อง");
+        assert_eq!(config.message("Correct", Some("fr")), "Output is correct");
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_compile_limit() -> GraderResult<()> {
+        let config = example_config()?;
+
+        let cpp = config.language("cpp").unwrap().compile_limit;
+        assert_eq!(cpp.time_limit, 10.0);
+        assert_eq!(cpp.memory_limit, 1024);
+
+        let rust = config.language("rust").unwrap().compile_limit;
+        assert_eq!(rust.time_limit, 30.0);
+        assert_eq!(rust.output_limit, 64);
+        Ok(())
+    }
+
+    #[test]
+    fn should_read_rerun_policy() -> GraderResult<()> {
+        let config = example_config()?;
+        assert_eq!(
+            config.rerun,
+            RerunPolicy {
+                margin: 0.05,
+                attempts: 3,
+                time: RerunTime::Median,
+            }
+        );
+
+        assert!(config.rerun.is_borderline(0.96, 1.0));
+        assert!(config.rerun.is_borderline(1.04, 1.0));
+        assert!(!config.rerun.is_borderline(0.5, 1.0));
+        assert!(!config.rerun.is_borderline(2.0, 1.0));
+        assert!(!RerunPolicy::default().is_borderline(1.0, 1.0));
+        Ok(())
+    }
+
+    #[test]
+    fn should_reject_invalid_config() -> GraderResult<()> {
+        dotenv::dotenv().ok();
+
+        let tmp_dir = TempDir::new("should_reject_invalid_config");
+        fs::create_dir(tmp_dir.0.join("scripts"))?;
+        let mut config = GraderConfig {
+            base_path: tmp_dir.0.clone(),
+            temporary_path: tmp_dir.0.clone(),
+            ..Default::default()
+        };
+
+        let write = |content: &str| fs::write(tmp_dir.0.join("scripts/config.yaml"), content);
+        write("language:\n  - id: \"cpp17\"\n    extension: \"cpp\"\n    compile_command: [\"g++\"]\n    run_command: [\"{binary}\"]\nmessage:\n  Correct: \"ok\"\n")?;
+        config.read_file()?;
+        config.validate()?;
+        assert_eq!(config.message("Correct", Some("th")), "ok");
+
+        write("language:\n  - id: \"cpp\"\n    extension: \"cpp\"\n")?;
+        config.read_file()?;
+        assert!(matches!(
+            config.validate(),
+            Err(GraderError::InvalidConfig { .. })
+        ));
+
+        write("language:\n  - id: \"cpp17\"\n    compile_command: [\"g++\"]\n    run_command: [\"{binary}\"]\n")?;
+        config.read_file()?;
+        assert!(config.validate().is_err());
+
+        write("rerun:\n  time: \"mean\"\n")?;
+        assert!(matches!(
+            config.read_file(),
+            Err(GraderError::InvalidConfig { .. })
+        ));
+
+        write("submission:\n  max_source_size: -1\n")?;
+        assert!(matches!(
+            config.read_file(),
+            Err(GraderError::InvalidConfig { .. })
+        ));
+
+        write("submission:\n  max_source_size: 9223372036854775807\n")?;
+        config.read_file()?;
+        assert!(config.validate().is_err());
+
+        write("compile:\n  cache_size: -1\n")?;
+        assert!(matches!(
+            config.read_file(),
+            Err(GraderError::InvalidConfig { .. })
+        ));
+
+        write("compile:\n  cache_size: 9223372036854775807\n")?;
+        config.read_file()?;
+        assert!(config.validate().is_err());
+
+        write("rerun:\n  attempts: -1\n")?;
+        assert!(matches!(
+            config.read_file(),
+            Err(GraderError::InvalidConfig { .. })
+        ));
+
+        write("rerun:\n  attempts: 0\n")?;
+        config.read_file()?;
+        assert!(config.validate().is_err());
+
+        config.boxes.count = 0;
+        assert!(config.validate().is_err());
+        config.boxes = BoxRange {
+            first: u64::MAX,
+            count: 1,
+        };
+        assert!(config.validate().is_err());
+        config.boxes = BoxRange::default();
+
+        config.temporary_path = tmp_dir.0.join("missing");
+        assert!(config.validate().is_err());
+        Ok(())
+    }
+}
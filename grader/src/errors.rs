@@ -43,10 +43,80 @@ pub enum GraderError {
         #[cfg(feature = "backtraces")]
         backtrace: Backtrace,
     },
-    #[error("Unkown: {msg}")]
-    Unknown {
+    #[error("Invalid task: {msg}")]
+    InvalidTask {
         msg: String,
-    }
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Invalid archive: {msg}")]
+    InvalidArchive {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Language not supported: {language}")]
+    LanguageNotSupported {
+        language: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Invalid source files: {msg}")]
+    InvalidSourceFiles {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Source limit exceeded: {msg}")]
+    SourceLimitExceeded {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Input limit exceeded: {msg}")]
+    InputLimitExceeded {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Compilation failed: {msg}")]
+    CompilationFailed {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Judge error: {msg}")]
+    JudgeError {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Stress test failed: {msg}")]
+    StressFailed {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Invalid path: {msg}")]
+    InvalidPath {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Invalid configuration: {msg}")]
+    InvalidConfig {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Timed out: {msg}")]
+    Timeout {
+        msg: String,
+        #[cfg(feature = "backtraces")]
+        backtrace: Backtrace,
+    },
+    #[error("Unkown: {msg}")]
+    Unknown { msg: String },
 }
 impl GraderError {
     pub fn invalid_utf8(msg: impl ToString) -> Self {
@@ -100,6 +170,102 @@ impl GraderError {
             backtrace: Backtrace::capture(),
         }
     }
+
+    pub fn invalid_task(msg: impl ToString) -> Self {
+        GraderError::InvalidTask {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn invalid_archive(msg: impl ToString) -> Self {
+        GraderError::InvalidArchive {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn language_not_supported(language: impl ToString) -> Self {
+        GraderError::LanguageNotSupported {
+            language: language.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn invalid_source_files(msg: impl ToString) -> Self {
+        GraderError::InvalidSourceFiles {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn source_limit_exceeded(msg: impl ToString) -> Self {
+        GraderError::SourceLimitExceeded {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn input_limit_exceeded(msg: impl ToString) -> Self {
+        GraderError::InputLimitExceeded {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn compilation_failed(msg: impl ToString) -> Self {
+        GraderError::CompilationFailed {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn judge_error(msg: impl ToString) -> Self {
+        GraderError::JudgeError {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn stress_failed(msg: impl ToString) -> Self {
+        GraderError::StressFailed {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn invalid_path(msg: impl ToString) -> Self {
+        GraderError::InvalidPath {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn invalid_config(msg: impl ToString) -> Self {
+        GraderError::InvalidConfig {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
+
+    pub fn timeout(msg: impl ToString) -> Self {
+        GraderError::Timeout {
+            msg: msg.to_string(),
+            #[cfg(feature = "backtraces")]
+            backtrace: Backtrace::capture(),
+        }
+    }
 }
 
 impl From<std::string::FromUtf8Error> for GraderError {
@@ -126,11 +292,25 @@ impl From<std::num::ParseFloatError> for GraderError {
     }
 }
 
+impl From<zip::result::ZipError> for GraderError {
+    fn from(source: zip::result::ZipError) -> Self {
+        Self::invalid_archive(source)
+    }
+}
+
+impl From<tokio::task::JoinError> for GraderError {
+    fn from(source: tokio::task::JoinError) -> Self {
+        Self::Unknown {
+            msg: source.to_string(),
+        }
+    }
+}
+
 impl From<anyhow::Error> for GraderError {
     fn from(value: anyhow::Error) -> Self {
         match value.downcast() {
             Ok(x) => x,
-            Err(e) => GraderError::Unknown { msg: e.to_string() }
+            Err(e) => GraderError::Unknown { msg: e.to_string() },
         }
     }
 }
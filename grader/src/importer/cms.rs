@@ -0,0 +1,258 @@
+//! Importer for the CMS `italy_yaml` task format.
+//!
+//! Testcases are read from `input/input{i}.txt` and `output/output{i}.txt`,
+//! subtasks from the `# ST: <score>` markers of `gen/GEN`, split into one
+//! group per testcase for `score_type: Sum`, and a comparator in
+//! `check/` or `cor/` is built and wrapped into a `checker`. A `sol/grader.cpp`
+//! or `sol/grader.c` stub is copied into `compile_files` together with the
+//! headers next to it.
+
+use super::{build_program, write_checker, write_testcases, ImportedGroup};
+use crate::errors::{GraderError, GraderResult};
+use crate::submission::manifest::Manifest;
+use crate::utils::load_yaml;
+use std::{collections::BTreeMap, fs, path::Path};
+
+const DEFAULT_TIME_LIMIT: f64 = 1.0;
+const DEFAULT_MEMORY_LIMIT: u64 = 256;
+const FULL_SCORE: f64 = 100.0;
+
+const COMPARATOR_WRAPPER: &str = r#"#!/bin/bash
+# Runs the CMS comparator, which takes the input, the correct output and the
+# contestant's output, and translates the score it prints in [0, 1] into the
+# checker protocol. The first line of its stderr is used as the message.
+DIR="$( cd "$( dirname "$0" )" && pwd )"
+STDERR_FILE="$(mktemp)"
+
+SCORE="$("${DIR}/cms_checker" "$1" "$3" "$2" 2> "${STDERR_FILE}" | head -n 1)"
+CODE=${PIPESTATUS[0]}
+MESSAGE="$(head -n 1 "${STDERR_FILE}")"
+rm -f "${STDERR_FILE}"
+
+case "${MESSAGE}" in
+  translate:*) MESSAGE="" ;;
+esac
+
+if [[ ${CODE} -ne 0 ]] || [[ -z "${SCORE}" ]]; then
+  echo "Judge Error"
+  echo "0"
+else
+  awk -v score="${SCORE}" 'BEGIN {
+    if (score >= 1) print "Correct";
+    else if (score <= 0) print "Incorrect";
+    else print "Partially Correct";
+    print score * 100;
+  }'
+fi
+echo "${MESSAGE}"
+"#;
+
+/// Import the CMS task at `source` into the task directory `destination`,
+/// whose name becomes the task id.
+pub fn import(source: &Path, destination: &Path) -> GraderResult<Manifest> {
+    let task_path = source.join("task.yaml");
+    if !task_path.is_file() {
+        return Err(GraderError::invalid_task("task.yaml not found"));
+    }
+    let task = load_yaml(&task_path)?;
+
+    for redirect in ["infile", "outfile"] {
+        if task[redirect].as_str().is_some_and(|file| !file.is_empty()) {
+            log::warn!("ignoring {redirect} in {task_path:?}, testcases are run through stdio");
+        }
+    }
+
+    let task_id = destination
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(GraderError::invalid_to_str())?
+        .to_owned();
+    fs::create_dir_all(destination)?;
+
+    let n_input = task["n_input"]
+        .as_i64()
+        .ok_or(GraderError::invalid_task("n_input not found in task.yaml"))?
+        as usize;
+    let tests = (0..n_input)
+        .map(|index| {
+            let input = source.join("input").join(format!("input{index}.txt"));
+            let output = source.join("output").join(format!("output{index}.txt"));
+            if !input.is_file() || !output.is_file() {
+                return Err(GraderError::invalid_task(format!(
+                    "missing input or output file for testcase {index}"
+                )));
+            }
+            Ok((input, output))
+        })
+        .collect::<GraderResult<Vec<_>>>()?;
+
+    // A Sum task scores every testcase on its own, so each one becomes a group
+    // worth its share of the subtask.
+    let sum = task["score_type"].as_str() == Some("Sum");
+    let mut groups = Vec::new();
+    let mut tests = tests.into_iter();
+    for (full_score, size) in subtasks(source, n_input)? {
+        let group_tests = tests.by_ref().take(size).collect::<Vec<_>>();
+        if group_tests.len() != size {
+            return Err(GraderError::invalid_task(
+                "gen/GEN lists more testcases than n_input",
+            ));
+        }
+        if sum {
+            groups.extend(group_tests.into_iter().map(|test| ImportedGroup {
+                full_score: full_score / size as f64,
+                tests: vec![test],
+                sample: false,
+            }));
+        } else {
+            groups.push(ImportedGroup {
+                full_score,
+                tests: group_tests,
+                sample: false,
+            });
+        }
+    }
+    if tests.next().is_some() {
+        return Err(GraderError::invalid_task(
+            "gen/GEN lists fewer testcases than n_input",
+        ));
+    }
+
+    let checker = match ["check", "cor"]
+        .iter()
+        .map(|directory| source.join(directory))
+        .find(|path| path.is_dir())
+    {
+        Some(comparator_path) => {
+            build_comparator(&comparator_path, &destination.join("cms_checker"))?;
+            write_checker(destination, COMPARATOR_WRAPPER)?;
+            None
+        }
+        None => Some(String::from("lcmp")),
+    };
+
+    let manifest = Manifest {
+        task_id,
+        time_limit: Some(
+            task["time_limit"]
+                .as_f64()
+                .or_else(|| task["time_limit"].as_i64().map(|x| x as f64))
+                .unwrap_or(DEFAULT_TIME_LIMIT),
+        ),
+        memory_limit: Some(
+            task["memory_limit"]
+                .as_i64()
+                .map_or(DEFAULT_MEMORY_LIMIT, |memory| memory as u64),
+        ),
+        compile_files: copy_graders(source, destination)?,
+        checker,
+        grouper: Some(String::from("min")),
+        groups: write_testcases(destination, &groups)?,
+        ..Default::default()
+    };
+    manifest.save(&destination.join("manifest.yaml"))?;
+    Ok(manifest)
+}
+
+/// Read `(full_score, number of testcases)` for every subtask from the
+/// `# ST: <score>` markers of `gen/GEN`, where every generator line and every
+/// `#COPY: <file>` line is a testcase. Testcases listed before the first
+/// marker form a zero-score group; without markers all `n_input` testcases
+/// form a single group.
+fn subtasks(source: &Path, n_input: usize) -> GraderResult<Vec<(f64, usize)>> {
+    let gen_path = source.join("gen").join("GEN");
+    if !gen_path.is_file() {
+        return Ok(vec![(FULL_SCORE, n_input)]);
+    }
+
+    let mut subtasks: Vec<(f64, usize)> = Vec::new();
+    let mut has_marker = false;
+    for line in fs::read_to_string(gen_path)?.lines() {
+        let line = line.trim();
+        let is_testcase = match line.strip_prefix('#').map(str::trim) {
+            Some(comment) => {
+                if let Some(score) = comment.strip_prefix("ST:") {
+                    subtasks.push((score.trim().parse()?, 0));
+                    has_marker = true;
+                }
+                // `#COPY: <file>` copies a testcase instead of generating it.
+                comment.starts_with("COPY:")
+            }
+            None => !line.is_empty(),
+        };
+        if is_testcase {
+            match subtasks.last_mut() {
+                Some((_, size)) => *size += 1,
+                None => subtasks.push((0.0, 1)),
+            }
+        }
+    }
+
+    if !has_marker {
+        return Ok(vec![(FULL_SCORE, n_input)]);
+    }
+    Ok(subtasks)
+}
+
+/// Build the comparator in `path`, preferring sources over prebuilt binaries.
+fn build_comparator(path: &Path, output: &Path) -> GraderResult<()> {
+    let has_sources = fs::read_dir(path)?.any(|entry| {
+        entry.is_ok_and(|entry| {
+            entry
+                .path()
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| ["cpp", "cc", "cxx", "c", "py"].contains(&extension))
+        })
+    });
+    if has_sources {
+        return build_program(path, output);
+    }
+
+    let binary = ["checker", "correttore"]
+        .iter()
+        .map(|name| path.join(name))
+        .find(|binary| binary.is_file())
+        .ok_or(GraderError::invalid_task(format!(
+            "no comparator found in {path:?}"
+        )))?;
+    fs::copy(binary, output)?;
+    Ok(())
+}
+
+/// Copy `sol/grader.<extension>` and the headers next to it into
+/// `compile_files`, returning the manifest entry compiling the stubs.
+fn copy_graders(
+    source: &Path,
+    destination: &Path,
+) -> GraderResult<Option<BTreeMap<String, Vec<String>>>> {
+    let sol_path = source.join("sol");
+    let mut compile_files = BTreeMap::new();
+    for (language, grader) in [("cpp", "grader.cpp"), ("c", "grader.c")] {
+        if sol_path.join(grader).is_file() {
+            compile_files.insert(language.to_owned(), vec![grader.to_owned()]);
+        }
+    }
+    if compile_files.is_empty() {
+        return Ok(None);
+    }
+
+    let compile_files_path = destination.join("compile_files");
+    fs::create_dir_all(&compile_files_path)?;
+    for entry in fs::read_dir(&sol_path)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let is_grader = compile_files
+            .values()
+            .flatten()
+            .any(|grader| file_name.to_str() == Some(grader));
+        let is_header = entry
+            .path()
+            .extension()
+            .is_some_and(|extension| extension == "h");
+        if is_grader || is_header {
+            fs::copy(entry.path(), compile_files_path.join(&file_name))?;
+        }
+    }
+    Ok(Some(compile_files))
+}
@@ -0,0 +1,252 @@
+//! Importer for the Kattis / ICPC problem package format.
+//!
+//! `data/sample` becomes a zero-score sample group, every directory below
+//! `data/secret` holding `.in` files becomes a group of its own, the default
+//! output validator is mapped onto one of the default checkers and a custom
+//! output validator is compiled and wrapped into a `checker`.
+
+use super::{build_program, write_checker, write_testcases, ImportedGroup};
+use crate::errors::{GraderError, GraderResult};
+use crate::submission::manifest::Manifest;
+use crate::utils::{load_yaml, shell_quote};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use yaml_rust::Yaml;
+
+const DEFAULT_TIME_LIMIT: f64 = 1.0;
+const DEFAULT_MEMORY_LIMIT: u64 = 2048;
+const FULL_SCORE: f64 = 100.0;
+
+const VALIDATOR_WRAPPER: &str = r#"#!/bin/bash
+# Runs the Kattis output validator and translates its exit code into the
+# checker protocol: 42 means accepted, 43 means wrong answer.
+DIR="$( cd "$( dirname "$0" )" && pwd )"
+FEEDBACK_DIR="$(mktemp -d)"
+
+"${DIR}/output_validator" "$1" "$3" "${FEEDBACK_DIR}" {flags} < "$2" > /dev/null 2>&1
+CODE=$?
+MESSAGE="$(head -n 1 "${FEEDBACK_DIR}/judgemessage.txt" 2> /dev/null)"
+rm -rf "${FEEDBACK_DIR}"
+
+if [[ ${CODE} -eq 42 ]]; then
+  echo "Correct"
+  echo "100"
+elif [[ ${CODE} -eq 43 ]]; then
+  echo "Incorrect"
+  echo "0"
+else
+  echo "Judge Error"
+  echo "0"
+fi
+echo "${MESSAGE}"
+"#;
+
+/// Import the Kattis package at `source` into the task directory
+/// `destination`, whose name becomes the task id.
+pub fn import(source: &Path, destination: &Path) -> GraderResult<Manifest> {
+    let problem_path = source.join("problem.yaml");
+    if !problem_path.is_file() {
+        return Err(GraderError::invalid_task("problem.yaml not found"));
+    }
+    let problem = load_yaml(&problem_path)?;
+
+    let task_id = destination
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(GraderError::invalid_to_str())?
+        .to_owned();
+    fs::create_dir_all(destination)?;
+
+    let mut groups = Vec::new();
+    let sample_path = source.join("data").join("sample");
+    if sample_path.is_dir() {
+        groups.push(ImportedGroup {
+            full_score: 0.0,
+            tests: collect_groups(&sample_path)?
+                .into_iter()
+                .flat_map(|(_, tests)| tests)
+                .collect(),
+            sample: true,
+        });
+    }
+    groups.extend(secret_groups(&source.join("data").join("secret"))?);
+    if groups.iter().all(|group| group.tests.is_empty()) {
+        return Err(GraderError::invalid_task("package has no testcases"));
+    }
+
+    let flags = problem["validator_flags"]
+        .as_str()
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>();
+    let validation = problem["validation"].as_str().unwrap_or("default");
+    let checker = match validation.split_whitespace().collect::<Vec<_>>()[..] {
+        [] | ["default"] => Some(default_checker(&flags).to_owned()),
+        ["custom"] => {
+            build_program(
+                &find_output_validator(source)?,
+                &destination.join("output_validator"),
+            )?;
+            let flags = flags
+                .iter()
+                .map(|flag| shell_quote(flag))
+                .collect::<Vec<_>>()
+                .join(" ");
+            write_checker(destination, &VALIDATOR_WRAPPER.replace("{flags}", &flags))?;
+            None
+        }
+        _ => {
+            return Err(GraderError::invalid_task(format!(
+                "unsupported validation type: {validation}"
+            )))
+        }
+    };
+
+    let manifest = Manifest {
+        task_id,
+        time_limit: Some(time_limit(source, &problem)?),
+        memory_limit: Some(
+            problem["limits"]["memory"]
+                .as_i64()
+                .map_or(DEFAULT_MEMORY_LIMIT, |memory| memory as u64),
+        ),
+        checker,
+        grouper: Some(String::from("min")),
+        groups: write_testcases(destination, &groups)?,
+        ..Default::default()
+    };
+    manifest.save(&destination.join("manifest.yaml"))?;
+    Ok(manifest)
+}
+
+/// Turn every directory below `data/secret` into a scored group. Groups
+/// declaring `range: "<min> <max>"` in their `testdata.yaml` are worth `max`,
+/// the rest of the full score is split evenly between the others.
+fn secret_groups(secret_path: &Path) -> GraderResult<Vec<ImportedGroup>> {
+    if !secret_path.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let groups = collect_groups(secret_path)?
+        .into_iter()
+        .map(|(path, tests)| -> GraderResult<_> {
+            let testdata_path = path.join("testdata.yaml");
+            let score = if testdata_path.is_file() {
+                load_yaml(&testdata_path)?["range"]
+                    .as_str()
+                    .and_then(|range| range.split_whitespace().nth(1))
+                    .map(|max| max.parse::<f64>())
+                    .transpose()?
+            } else {
+                None
+            };
+            Ok((score, tests))
+        })
+        .collect::<GraderResult<Vec<_>>>()?;
+
+    let declared_score: f64 = groups.iter().filter_map(|(score, _)| *score).sum();
+    let undeclared = groups.iter().filter(|(score, _)| score.is_none()).count();
+    let remaining_score = if undeclared > 0 {
+        (FULL_SCORE - declared_score).max(0.0) / undeclared as f64
+    } else {
+        0.0
+    };
+
+    Ok(groups
+        .into_iter()
+        .map(|(score, tests)| ImportedGroup {
+            full_score: score.unwrap_or(remaining_score),
+            tests,
+            sample: false,
+        })
+        .collect())
+}
+
+type TestcaseDirectory = (PathBuf, Vec<(PathBuf, PathBuf)>);
+
+/// Walk `path` depth first and return every directory holding testcases,
+/// with its `(input, answer)` pairs sorted by name.
+fn collect_groups(path: &Path) -> GraderResult<Vec<TestcaseDirectory>> {
+    let mut entries = fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<GraderResult<Vec<_>>>()?;
+    entries.sort();
+
+    let mut tests = Vec::new();
+    let mut groups = Vec::new();
+    for entry in entries {
+        if entry.is_dir() {
+            groups.extend(collect_groups(&entry)?);
+        } else if entry.extension().is_some_and(|extension| extension == "in") {
+            let answer = entry.with_extension("ans");
+            if !answer.is_file() {
+                return Err(GraderError::invalid_task(format!(
+                    "missing answer file for {entry:?}"
+                )));
+            }
+            tests.push((entry, answer));
+        }
+    }
+
+    if !tests.is_empty() {
+        groups.insert(0, (path.to_path_buf(), tests));
+    }
+    Ok(groups)
+}
+
+/// Map the flags of the default output validator onto the closest default checker.
+fn default_checker(flags: &[&str]) -> &'static str {
+    let tolerance = flags
+        .windows(2)
+        .filter(|pair| {
+            matches!(
+                pair[0],
+                "float_tolerance" | "float_absolute_tolerance" | "float_relative_tolerance"
+            )
+        })
+        .filter_map(|pair| pair[1].parse::<f64>().ok())
+        .reduce(f64::min);
+
+    match tolerance {
+        Some(tolerance) if tolerance <= 1e-9 => "rcmp9",
+        Some(_) => "rcmp6",
+        None => "wcmp",
+    }
+}
+
+fn find_output_validator(source: &Path) -> GraderResult<PathBuf> {
+    let validator_path = source.join("output_validator");
+    if validator_path.exists() {
+        return Ok(validator_path);
+    }
+
+    let validators_path = source.join("output_validators");
+    if validators_path.is_dir() {
+        let mut validators = fs::read_dir(&validators_path)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<GraderResult<Vec<_>>>()?;
+        validators.sort();
+        if let Some(validator) = validators.into_iter().next() {
+            return Ok(validator);
+        }
+    }
+
+    Err(GraderError::invalid_task(
+        "custom output validator not found",
+    ))
+}
+
+fn time_limit(source: &Path, problem: &Yaml) -> GraderResult<f64> {
+    let timelimit_path = source.join(".timelimit");
+    if timelimit_path.is_file() {
+        return Ok(fs::read_to_string(timelimit_path)?.trim().parse()?);
+    }
+
+    let time_limit = &problem["limits"]["time_limit"];
+    Ok(time_limit
+        .as_f64()
+        .or_else(|| time_limit.as_i64().map(|x| x as f64))
+        .unwrap_or(DEFAULT_TIME_LIMIT))
+}
@@ -0,0 +1,136 @@
+//! Importers translating problem packages from other judges into the native
+//! task layout: `manifest.yaml`, `testcases/{index}.in`, `testcases/{index}.sol`
+//! and, for tasks with a custom checker, an executable `checker` speaking the
+//! checker protocol described in the README.
+
+use crate::errors::{GraderError, GraderResult};
+use crate::submission::manifest::Group;
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+pub mod cms;
+pub mod kattis;
+
+#[cfg(test)]
+mod tests;
+
+/// A group of testcases found in a foreign package, as `(input, solution)` pairs
+/// in the order they should be numbered.
+pub struct ImportedGroup {
+    pub full_score: f64,
+    pub tests: Vec<(PathBuf, PathBuf)>,
+    pub sample: bool,
+}
+
+/// Copy every testcase into `destination/testcases`, numbering them from 1 in
+/// group order, and return the groups in the form expected by `Manifest`.
+pub fn write_testcases(destination: &Path, groups: &[ImportedGroup]) -> GraderResult<Vec<Group>> {
+    let testcases_path = destination.join("testcases");
+    fs::create_dir_all(&testcases_path)?;
+
+    let mut index = 1;
+    let mut manifest_groups = Vec::new();
+    for group in groups {
+        for (input, solution) in &group.tests {
+            fs::copy(input, testcases_path.join(format!("{index}.in")))?;
+            fs::copy(solution, testcases_path.join(format!("{index}.sol")))?;
+            index += 1;
+        }
+        manifest_groups.push(Group {
+            sample: group.sample,
+            ..Group::count(group.full_score, group.tests.len() as u64)
+        });
+    }
+    Ok(manifest_groups)
+}
+
+/// Build the program found at `source` (a single file or a directory of
+/// sources) into the executable `output`. C and C++ sources are compiled,
+/// a lone Python file is wrapped in a launcher script.
+pub fn build_program(source: &Path, output: &Path) -> GraderResult<()> {
+    let sources = if source.is_dir() {
+        let mut sources = fs::read_dir(source)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<GraderResult<Vec<_>>>()?;
+        sources.sort();
+        sources
+    } else {
+        vec![source.to_path_buf()]
+    };
+    let include_path = if source.is_dir() {
+        source
+    } else {
+        source.parent().ok_or(GraderError::invalid_value())?
+    };
+
+    let with_extension = |extensions: &[&str]| -> Vec<&PathBuf> {
+        sources
+            .iter()
+            .filter(|path| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| extensions.contains(&extension))
+            })
+            .collect()
+    };
+
+    let cpp_sources = with_extension(&["cpp", "cc", "cxx"]);
+    let c_sources = with_extension(&["c"]);
+    let python_sources = with_extension(&["py"]);
+
+    let (compiler, flags, sources) = if !cpp_sources.is_empty() {
+        ("g++", ["-std=c++17", "-O2"], cpp_sources)
+    } else if !c_sources.is_empty() {
+        ("gcc", ["-std=c11", "-O2"], c_sources)
+    } else if let [script] = python_sources[..] {
+        let script_path = output.with_extension("py");
+        fs::copy(script, &script_path)?;
+        let script_name = script_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(GraderError::invalid_to_str())?;
+        fs::write(
+            output,
+            format!("#!/bin/bash\nexec python3 \"$(dirname \"$0\")/{script_name}\" \"$@\"\n"),
+        )?;
+        return make_executable(output);
+    } else {
+        return Err(GraderError::invalid_task(format!(
+            "no buildable sources found in {source:?}"
+        )));
+    };
+
+    let compile_output = Command::new(compiler)
+        .args(flags)
+        .arg("-I")
+        .arg(include_path)
+        .args(sources)
+        .arg("-o")
+        .arg(output)
+        .output()?;
+    if !compile_output.status.success() {
+        return Err(GraderError::invalid_task(format!(
+            "unable to compile {source:?}: {}",
+            String::from_utf8_lossy(&compile_output.stderr)
+        )));
+    }
+    Ok(())
+}
+
+/// Write `script` as the task's executable `checker`.
+pub fn write_checker(destination: &Path, script: &str) -> GraderResult<()> {
+    let checker_path = destination.join("checker");
+    fs::write(&checker_path, script)?;
+    make_executable(&checker_path)
+}
+
+fn make_executable(path: &Path) -> GraderResult<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(0o755);
+    fs::set_permissions(path, permissions)?;
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use super::*;
+
+use crate::config;
+use crate::errors::GraderResult;
+use crate::submission::manifest::{Group, Manifest};
+use crate::submission::Submission;
+use crate::utils::tests::TempDir;
+use dotenv::dotenv;
+use futures::sink::drain;
+use std::fs;
+
+fn write_file(path: PathBuf, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+#[test]
+fn should_import_kattis_package() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_kattis_package");
+    let source = tmp_dir.0.join("source");
+    let destination = tmp_dir.0.join("kattis_task");
+
+    write_file(
+        source.join("problem.yaml"),
+        "name: A plus B\nlimits:\n  memory: 256\n",
+    );
+    write_file(source.join(".timelimit"), "2\n");
+    write_file(source.join("data/sample/1.in"), "1 2\n");
+    write_file(source.join("data/sample/1.ans"), "3\n");
+    write_file(
+        source.join("data/secret/group1/testdata.yaml"),
+        "range: 0 30\n",
+    );
+    write_file(source.join("data/secret/group1/a.in"), "2 2\n");
+    write_file(source.join("data/secret/group1/a.ans"), "4\n");
+    write_file(source.join("data/secret/group2/a.in"), "5 5\n");
+    write_file(source.join("data/secret/group2/a.ans"), "10\n");
+    write_file(source.join("data/secret/group2/b.in"), "7 5\n");
+    write_file(source.join("data/secret/group2/b.ans"), "12\n");
+
+    let manifest = kattis::import(&source, &destination)?;
+
+    assert_eq!(manifest.task_id, "kattis_task");
+    assert_eq!(manifest.time_limit, Some(2.0));
+    assert_eq!(manifest.memory_limit, Some(256));
+    assert_eq!(manifest.checker.as_deref(), Some("wcmp"));
+    assert_eq!(
+        manifest.groups,
+        vec![
+            Group {
+                sample: true,
+                ..Group::count(0.0, 1)
+            },
+            Group::count(30.0, 1),
+            Group::count(70.0, 2)
+        ]
+    );
+    assert_eq!(
+        fs::read_to_string(destination.join("testcases").join("4.sol"))?,
+        "12\n"
+    );
+
+    let saved = Manifest::from(destination.join("manifest.yaml"))?;
+    assert_eq!(saved.groups, manifest.groups);
+    assert_eq!(saved.time_limit, manifest.time_limit);
+
+    Ok(())
+}
+
+#[test]
+fn should_map_kattis_float_tolerance() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_kattis_float_tolerance");
+    let source = tmp_dir.0.join("source");
+
+    write_file(
+        source.join("problem.yaml"),
+        "validator_flags: float_tolerance 1e-9\n",
+    );
+    write_file(source.join("data/secret/1.in"), "1\n");
+    write_file(source.join("data/secret/1.ans"), "1.0\n");
+
+    let manifest = kattis::import(&source, &tmp_dir.0.join("task"))?;
+
+    assert_eq!(manifest.checker.as_deref(), Some("rcmp9"));
+    assert_eq!(manifest.groups, vec![Group::count(100.0, 1)]);
+
+    Ok(())
+}
+
+#[test]
+fn should_wrap_kattis_output_validator() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_kattis_output_validator");
+    let source = tmp_dir.0.join("source");
+    let destination = tmp_dir.0.join("task");
+
+    write_file(source.join("problem.yaml"), "validation: custom\n");
+    write_file(source.join("data/secret/1.in"), "1\n");
+    write_file(source.join("data/secret/1.ans"), "1\n");
+    write_file(
+        source.join("output_validators/validator/validator.py"),
+        "import sys\nsys.exit(42 if sys.stdin.read().split() == ['1'] else 43)\n",
+    );
+
+    let manifest = kattis::import(&source, &destination)?;
+    assert_eq!(manifest.checker, None);
+
+    let output_path = tmp_dir.0.join("output");
+    fs::write(&output_path, "1\n")?;
+    let checker_output = std::process::Command::new(destination.join("checker"))
+        .arg(destination.join("testcases").join("1.in"))
+        .arg(&output_path)
+        .arg(destination.join("testcases").join("1.sol"))
+        .output()?;
+
+    assert_eq!(
+        String::from_utf8(checker_output.stdout)?
+            .lines()
+            .collect::<Vec<_>>(),
+        vec!["Correct", "100", ""]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn should_import_cms_task() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_cms_task");
+    let source = tmp_dir.0.join("source");
+    let destination = tmp_dir.0.join("cms_task");
+
+    write_file(
+        source.join("task.yaml"),
+        "name: cms_task\ntime_limit: 1.5\nmemory_limit: 128\nn_input: 4\n",
+    );
+    write_file(
+        source.join("gen/GEN"),
+        "# ST: 0\n1 2\n# ST: 40\n2 2\n# ST: 60\n3 3\n4 4\n",
+    );
+    for (index, answer) in ["3", "4", "6", "8"].iter().enumerate() {
+        write_file(source.join(format!("input/input{index}.txt")), "");
+        write_file(source.join(format!("output/output{index}.txt")), answer);
+    }
+    write_file(source.join("sol/grader.cpp"), "");
+    write_file(source.join("sol/cms_task.h"), "");
+
+    let manifest = cms::import(&source, &destination)?;
+
+    assert_eq!(manifest.task_id, "cms_task");
+    assert_eq!(manifest.time_limit, Some(1.5));
+    assert_eq!(manifest.memory_limit, Some(128));
+    assert_eq!(manifest.checker.as_deref(), Some("lcmp"));
+    assert_eq!(manifest.grouper.as_deref(), Some("min"));
+    assert_eq!(
+        manifest.groups,
+        vec![
+            Group::count(0.0, 1),
+            Group::count(40.0, 1),
+            Group::count(60.0, 2)
+        ]
+    );
+    assert_eq!(
+        manifest.compile_files.unwrap()["cpp"],
+        vec![String::from("grader.cpp")]
+    );
+    assert!(destination
+        .join("compile_files")
+        .join("cms_task.h")
+        .is_file());
+    assert_eq!(
+        fs::read_to_string(destination.join("testcases").join("4.sol"))?,
+        "8"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn should_count_cms_copied_testcases() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_cms_copied_testcases");
+    let source = tmp_dir.0.join("source");
+    let destination = tmp_dir.0.join("task");
+
+    write_file(
+        source.join("task.yaml"),
+        "n_input: 3
+",
+    );
+    write_file(
+        source.join("gen/GEN"),
+        "# ST: 30\n#COPY: sample.txt\n# ST: 70\n# COPY: big.txt\n5 5\n",
+    );
+    for index in 0..3 {
+        write_file(source.join(format!("input/input{index}.txt")), "");
+        write_file(source.join(format!("output/output{index}.txt")), "");
+    }
+
+    let manifest = cms::import(&source, &destination)?;
+    assert_eq!(
+        manifest.groups,
+        vec![Group::count(30.0, 1), Group::count(70.0, 2)]
+    );
+
+    write_file(source.join("gen/GEN"), "# ST: 100\n5 5\n6 6\n");
+    assert!(matches!(
+        cms::import(&source, &tmp_dir.0.join("fewer")),
+        Err(GraderError::InvalidTask { .. })
+    ));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn should_score_cms_sum_task_per_testcase() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_cms_sum_task");
+    let source = tmp_dir.0.join("source");
+    let destination = TempDir(config::get()?.tasks_path().join("cms_sum_task"));
+
+    write_file(source.join("task.yaml"), "score_type: Sum\nn_input: 4\n");
+    for (index, (input, answer)) in [("1 2", "3"), ("2 2", "4"), ("3 3", "6"), ("4 4", "8")]
+        .iter()
+        .enumerate()
+    {
+        write_file(source.join(format!("input/input{index}.txt")), input);
+        write_file(source.join(format!("output/output{index}.txt")), answer);
+    }
+
+    let manifest = cms::import(&source, &destination.0)?;
+    assert_eq!(manifest.grouper.as_deref(), Some("min"));
+    assert_eq!(manifest.groups, vec![Group::count(25.0, 1); 4]);
+
+    // Multiplying only gets the second testcase right.
+    let code =
+        "#include <iostream>\nint main() { int a, b; std::cin >> a >> b; std::cout << a * b; }\n";
+    let mut submission =
+        Submission::try_from("cms_sum_task", "import_cms_sum", "cpp", &[code], drain()).await?;
+    assert!(submission.compile().await?);
+
+    let result = submission.run().await?;
+    assert_eq!(result.score, 25.0);
+
+    Ok(())
+}
+
+#[test]
+fn should_wrap_cms_comparator() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("import_cms_comparator");
+    let source = tmp_dir.0.join("source");
+    let destination = tmp_dir.0.join("task");
+
+    write_file(source.join("task.yaml"), "n_input: 1\n");
+    write_file(source.join("input/input0.txt"), "4\n");
+    write_file(source.join("output/output0.txt"), "2 2\n");
+    write_file(
+        source.join("check/checker.py"),
+        "import sys\nprint(0.5)\nsys.stderr.write('half of the pairs\\n')\n",
+    );
+
+    let manifest = cms::import(&source, &destination)?;
+    assert_eq!(manifest.checker, None);
+    assert_eq!(manifest.groups, vec![Group::count(100.0, 1)]);
+
+    let output_path = tmp_dir.0.join("output");
+    fs::write(&output_path, "1 3\n")?;
+    let checker_output = std::process::Command::new(destination.join("checker"))
+        .arg(destination.join("testcases").join("1.in"))
+        .arg(&output_path)
+        .arg(destination.join("testcases").join("1.sol"))
+        .output()?;
+
+    assert_eq!(
+        String::from_utf8(checker_output.stdout)?
+            .lines()
+            .collect::<Vec<_>>(),
+        vec!["Partially Correct", "50", "half of the pairs"]
+    );
+
+    Ok(())
+}
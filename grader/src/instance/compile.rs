@@ -0,0 +1,188 @@
+use super::{parse_log, InstanceResult, DEFAULT_PROCESSES};
+use crate::combine_argument;
+use crate::config::GraderConfig;
+use crate::errors::{GraderError, GraderResult};
+use crate::utils::output_with_timeout;
+use anyhow::Context;
+use std::{os::unix::fs::PermissionsExt, path::Path, path::PathBuf, sync::Arc, time::Duration};
+use tokio::{fs, process::Command};
+use yaml_rust::Yaml;
+
+/// Resources a compile script may use, read per language from `config.yaml`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CompileLimit {
+    /// CPU time in seconds.
+    pub time_limit: f64,
+    /// Memory in megabytes.
+    pub memory_limit: u64,
+    /// Size of any single file written, in megabytes.
+    pub output_limit: u64,
+}
+
+impl Default for CompileLimit {
+    fn default() -> Self {
+        CompileLimit {
+            time_limit: 10.0,
+            memory_limit: 1024,
+            output_limit: 64,
+        }
+    }
+}
+
+impl CompileLimit {
+    /// Read the limits set in a `compile` entry of `config.yaml`, taking the
+    /// others from `fallback`.
+    pub fn from_yaml(yaml: &Yaml, fallback: CompileLimit) -> Self {
+        let integer = |key: &str| yaml[key].as_i64().map(|x| x as u64);
+        CompileLimit {
+            time_limit: yaml["time_limit"]
+                .as_f64()
+                .or_else(|| yaml["time_limit"].as_i64().map(|x| x as f64))
+                .unwrap_or(fallback.time_limit),
+            memory_limit: integer("memory_limit").unwrap_or(fallback.memory_limit),
+            output_limit: integer("output_limit").unwrap_or(fallback.output_limit),
+        }
+    }
+}
+
+/// CompileInstance runs a compile script inside an isolate box, with
+/// `work_path` mounted read-write so the script can write the binary and
+/// `compileMsg` next to the sources.
+#[derive(Default, Debug)]
+pub struct CompileInstance {
+    pub box_id: u64,
+    pub box_path: PathBuf,
+    pub log_file: PathBuf,
+    pub work_path: PathBuf,
+    pub script_path: PathBuf,
+    pub args: Vec<PathBuf>,
+    pub limit: CompileLimit,
+    /// Process limit, 128 when unset.
+    pub processes: Option<u64>,
+    /// Extra directories mounted read-only in the box.
+    pub sandbox_dirs: Vec<String>,
+    pub config: Arc<GraderConfig>,
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct CompileOutput {
+    pub result: InstanceResult,
+    /// What the compile script printed, its return code and binary path.
+    pub stdout: String,
+}
+
+impl CompileInstance {
+    pub(crate) fn get_run_arguments(&self) -> GraderResult<Vec<String>> {
+        let script_dir = self
+            .script_path
+            .parent()
+            .ok_or(GraderError::invalid_value())?;
+
+        let mut args = combine_argument![
+            "-b",
+            self.box_id.to_string(),
+            "-M",
+            path_to_str(&self.log_file)?,
+            "-t",
+            self.limit.time_limit.to_string(),
+            "-w",
+            (self.limit.time_limit + 5.0).to_string(),
+            "-x",
+            "1",
+            "-f",
+            (self.limit.output_limit * 1024).to_string(),
+            "-E",
+            "PATH",
+            "-E",
+            "HOME=/box",
+            format!(
+                "--processes={}",
+                self.processes.unwrap_or(DEFAULT_PROCESSES)
+            ),
+            "--cg",
+            format!("--cg-mem={}", self.limit.memory_limit * 1024),
+            format!("--dir={}:rw", path_to_str(&self.work_path)?),
+            format!("--dir={}", self.config.alternative_path.display())
+        ];
+        // Generated compile scripts live in the working directory itself.
+        if script_dir != self.work_path {
+            args.push(format!("--dir={}", path_to_str(script_dir)?));
+        }
+        args.extend(self.sandbox_dirs.iter().map(|dir| format!("--dir={dir}")));
+        args.extend(combine_argument![
+            "--run",
+            "--",
+            path_to_str(&self.script_path)?,
+            path_to_str(&self.work_path)?
+        ]);
+        for arg in &self.args {
+            args.push(path_to_str(arg)?.to_string());
+        }
+        Ok(args)
+    }
+
+    pub async fn init(&mut self) -> GraderResult<()> {
+        let box_path = Command::new(&self.config.isolate_path)
+            .args(["--init", "--cg", "-b"])
+            .arg(format!("{}", self.box_id))
+            .output()
+            .await?;
+
+        let box_path = String::from_utf8(box_path.stdout)?;
+        self.box_path = PathBuf::from(box_path.trim_end_matches('\n')).join("box");
+
+        self.log_file = fs::canonicalize(&self.config.temporary_path)
+            .await?
+            .join(format!("tmp_compile_log_{}.txt", self.box_id));
+
+        // The box runs as an unprivileged user which has to write the binary.
+        // It may create files and open the sources by name, but not list the
+        // directory or remove files it did not create.
+        self.work_path = fs::canonicalize(&self.work_path)
+            .await
+            .with_context(|| format!("Unable to resolve {:?}", self.work_path))?;
+        fs::set_permissions(&self.work_path, std::fs::Permissions::from_mode(0o1733)).await?;
+
+        self.script_path = fs::canonicalize(&self.script_path)
+            .await
+            .with_context(|| format!("Unable to resolve {:?}", self.script_path))?;
+        for arg in self.args.iter_mut() {
+            *arg = fs::canonicalize(&arg).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn run(&self) -> GraderResult<CompileOutput> {
+        let args = self.get_run_arguments()?;
+        log::debug!("compile arguments: {args:?}");
+        // isolate enforces the wall time itself, this only guards against it hanging.
+        let timeout = Duration::from_secs_f64(self.limit.time_limit + 30.0);
+        let output =
+            output_with_timeout(Command::new(&self.config.isolate_path).args(args), timeout)
+                .await?;
+
+        let log_content = fs::read_to_string(&self.log_file).await?;
+        Ok(CompileOutput {
+            result: parse_log(&log_content, self.limit.memory_limit * 1024)?,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        })
+    }
+}
+
+fn path_to_str(path: &Path) -> GraderResult<&str> {
+    path.to_str().ok_or(GraderError::invalid_to_str())
+}
+
+impl Drop for CompileInstance {
+    fn drop(&mut self) {
+        std::process::Command::new(&self.config.isolate_path)
+            .args(["--cleanup", "--cg", "-b"])
+            .arg(self.box_id.to_string())
+            .output()
+            .ok();
+
+        if self.log_file.is_file() {
+            std::fs::remove_file(&self.log_file).ok();
+        }
+    }
+}
@@ -1,9 +1,21 @@
 use crate::combine_argument;
+use crate::config::GraderConfig;
 use crate::errors::{GraderError, GraderResult};
-use crate::utils::get_env;
-use std::path::PathBuf;
-use tokio::{fs, process::Command};
 use anyhow::Context;
+use std::{path::PathBuf, sync::Arc};
+use tokio::{fs, io::AsyncReadExt, process::Command};
+
+/// Bytes of the program's stderr kept when copying it out of the box.
+pub const STDERR_LIMIT: u64 = 64 * 1024;
+
+pub(crate) const DEFAULT_PROCESSES: u64 = 128;
+
+/// Size in kilobytes of any single file a program may write, its output and
+/// stderr included.
+pub const OUTPUT_LIMIT: u64 = 64 * 1024;
+
+pub mod compile;
+pub mod pool;
 
 #[cfg(test)]
 mod tests;
@@ -19,11 +31,19 @@ pub struct Instance {
     pub memory_limit: u64,
     pub input_path: PathBuf,
     pub output_path: PathBuf,
+    /// Where the program's stderr is copied after the run, left out when empty.
+    pub stderr_path: PathBuf,
     pub runner_path: PathBuf,
+    /// Process limit, 128 when unset.
+    pub processes: Option<u64>,
+    /// Extra directories mounted read-only in the box.
+    pub sandbox_dirs: Vec<String>,
+    pub config: Arc<GraderConfig>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Default, Debug, PartialEq)]
 pub enum RunVerdict {
+    #[default]
     VerdictOK,
     VerdictTLE,
     VerdictMLE,
@@ -32,12 +52,6 @@ pub enum RunVerdict {
     VerdictSG,
 }
 
-impl Default for RunVerdict {
-    fn default() -> Self {
-        Self::VerdictOK
-    }
-}
-
 #[derive(Default, PartialEq, Debug)]
 pub struct InstanceResult {
     pub status: RunVerdict,
@@ -47,7 +61,7 @@ pub struct InstanceResult {
 
 impl Instance {
     fn get_run_arguments(&self) -> GraderResult<Vec<String>> {
-        Ok(combine_argument![
+        let mut args = combine_argument![
             "-b",
             self.box_id.to_string(),
             "-M",
@@ -66,54 +80,30 @@ impl Instance {
             "input",
             "-o",
             "output",
-            "--processes=128",
+            "-r",
+            "stderr",
+            "-f",
+            OUTPUT_LIMIT,
+            format!(
+                "--processes={}",
+                self.processes.unwrap_or(DEFAULT_PROCESSES)
+            ),
             "--cg",
             format!("--cg-mem={}", self.memory_limit),
-            format!("--dir={}", get_env("ALTERNATIVE_PATH")),
-            "--run",
-            "--",
-            "runner"
-        ])
+            format!("--dir={}", self.config.alternative_path.display())
+        ];
+        args.extend(self.sandbox_dirs.iter().map(|dir| format!("--dir={dir}")));
+        args.extend(combine_argument!["--run", "--", "runner"]);
+        Ok(args)
     }
 
     pub async fn get_result(&self) -> GraderResult<InstanceResult> {
         let log_content = fs::read_to_string(&self.log_file).await?;
-        let mut result: InstanceResult = Default::default();
-        let mut memory_limit_exceeded = false;
-        for log_line in log_content.lines() {
-            let args: Vec<&str> = log_line.split(':').collect();
-            log::info!("{args:?}");
-            let args: Vec<&str> = log_line.split(':').collect();
-            if args.len() >= 2 {
-                match args[0] {
-                    "status" => {
-                        result.status = match args[1] {
-                            "RE" => RunVerdict::VerdictRE,
-                            "SG" => RunVerdict::VerdictSG,
-                            "TO" => RunVerdict::VerdictTLE,
-                            "XX" => RunVerdict::VerdictXX,
-                            _ => RunVerdict::VerdictSG,
-                        }
-                    }
-                    "time" => result.time_usage = args[1].parse()?,
-                    "cg-mem" => result.memory_usage = args[1].parse()?,
-                    "cg-oom-killed" => memory_limit_exceeded = args[1].trim() == "1",
-                    _ => (),
-                }
-            }
-        }
-        if memory_limit_exceeded
-            || result.memory_usage >= self.memory_limit && result.status == Default::default()
-        {
-            result.status = RunVerdict::VerdictMLE;
-        }
-        Ok(result)
+        parse_log(&log_content, self.memory_limit)
     }
 
     pub async fn init(&mut self) -> GraderResult<()> {
-        let tmp_path = get_env("TEMPORARY_PATH");
-
-        let box_path = Command::new(get_env("ISOLATE_PATH"))
+        let box_path = Command::new(&self.config.isolate_path)
             .args(["--init", "--cg", "-b"])
             .arg(format!("{}", self.box_id))
             .output()
@@ -122,12 +112,17 @@ impl Instance {
         let box_path = String::from_utf8(box_path.stdout)?;
         self.box_path = PathBuf::from(box_path.trim_end_matches('\n')).join("box");
 
-        self.log_file = PathBuf::from(tmp_path).join(format!("tmp_log_{}.txt", self.box_id));
+        self.log_file = self
+            .config
+            .temporary_path
+            .join(format!("tmp_log_{}.txt", self.box_id));
 
         let from = self.box_path.as_path();
         let to = self.log_file.as_path();
 
-        fs::copy(&self.input_path, &self.box_path.join("input")).await.with_context(|| format!("Copy from {from:?} to {to:?}"))?;
+        fs::copy(&self.input_path, &self.box_path.join("input"))
+            .await
+            .with_context(|| format!("Copy from {from:?} to {to:?}"))?;
 
         let from = self.bin_path.as_path();
         let to = self.box_path.join(
@@ -137,22 +132,21 @@ impl Instance {
                 .with_context(|| "Cannot convert OsStr to str")?,
         );
 
-        fs::copy(
-            from,
-            to.as_path(),
-        )
-        .await
-        .with_context(|| format!("Copy from {from:?} to {to:?}"))?;
+        fs::copy(from, to.as_path())
+            .await
+            .with_context(|| format!("Copy from {from:?} to {to:?}"))?;
 
         let from = self.runner_path.as_path();
         let to = self.box_path.join("runner");
-        fs::copy(from, to.as_path()).await.with_context(|| "Copy from {from:?} to {to:?}")?;
+        fs::copy(from, to.as_path())
+            .await
+            .with_context(|| "Copy from {from:?} to {to:?}")?;
         Ok(())
     }
 
     pub async fn run(&self) -> GraderResult<InstanceResult> {
         let args = self.get_run_arguments()?;
-        Command::new(get_env("ISOLATE_PATH"))
+        Command::new(&self.config.isolate_path)
             .args(args)
             .output()
             .await?;
@@ -162,15 +156,68 @@ impl Instance {
             let from = self.box_path.join("output");
             let to = self.output_path.as_path();
 
-            fs::copy(from, to).await.with_context(|| "Copy from {from:?} to {to:?}")?;
+            fs::copy(from, to)
+                .await
+                .with_context(|| "Copy from {from:?} to {to:?}")?;
+        }
+        if self.stderr_path != PathBuf::new() {
+            self.copy_stderr().await?;
         }
         Ok(result)
     }
+
+    async fn copy_stderr(&self) -> GraderResult<()> {
+        let from = self.box_path.join("stderr");
+        let to = self.stderr_path.as_path();
+
+        let mut stderr = Vec::new();
+        if let Ok(file) = fs::File::open(&from).await {
+            file.take(STDERR_LIMIT).read_to_end(&mut stderr).await?;
+        }
+        fs::write(to, stderr)
+            .await
+            .with_context(|| format!("Copy from {from:?} to {to:?}"))?;
+        Ok(())
+    }
+}
+
+/// Read the verdict and resource usage from an isolate meta file, where
+/// `memory_limit` is the cgroup memory limit in kilobytes.
+pub(crate) fn parse_log(log_content: &str, memory_limit: u64) -> GraderResult<InstanceResult> {
+    let mut result: InstanceResult = Default::default();
+    let mut memory_limit_exceeded = false;
+    for log_line in log_content.lines() {
+        let args: Vec<&str> = log_line.split(':').collect();
+        log::info!("{args:?}");
+        if args.len() >= 2 {
+            match args[0] {
+                "status" => {
+                    result.status = match args[1] {
+                        "RE" => RunVerdict::VerdictRE,
+                        "SG" => RunVerdict::VerdictSG,
+                        "TO" => RunVerdict::VerdictTLE,
+                        "XX" => RunVerdict::VerdictXX,
+                        _ => RunVerdict::VerdictSG,
+                    }
+                }
+                "time" => result.time_usage = args[1].parse()?,
+                "cg-mem" => result.memory_usage = args[1].parse()?,
+                "cg-oom-killed" => memory_limit_exceeded = args[1].trim() == "1",
+                _ => (),
+            }
+        }
+    }
+    if memory_limit_exceeded
+        || result.memory_usage >= memory_limit && result.status == Default::default()
+    {
+        result.status = RunVerdict::VerdictMLE;
+    }
+    Ok(result)
 }
 
 impl Drop for Instance {
     fn drop(&mut self) {
-        std::process::Command::new(get_env("ISOLATE_PATH"))
+        std::process::Command::new(&self.config.isolate_path)
             .args(["--cleanup", "--cg", "-b"])
             .arg(self.box_id.to_string())
             .output()
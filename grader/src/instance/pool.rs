@@ -0,0 +1,57 @@
+//! Isolate boxes handed out to sandboxed runs, so two runs never share a box.
+
+use crate::config::BoxRange;
+use std::{
+    collections::BTreeSet,
+    sync::{Mutex, OnceLock},
+};
+use tokio::sync::Notify;
+
+static IN_USE: Mutex<BTreeSet<u64>> = Mutex::new(BTreeSet::new());
+
+fn released() -> &'static Notify {
+    static RELEASED: OnceLock<Notify> = OnceLock::new();
+    RELEASED.get_or_init(Notify::new)
+}
+
+/// A box reserved until the lease is dropped.
+#[derive(Debug)]
+pub struct BoxLease {
+    id: u64,
+}
+
+impl BoxLease {
+    /// Reserve the lowest free box of `range`, waiting while every box of it
+    /// is in use.
+    ///
+    /// A caller must not hold another lease while waiting here, or runs that
+    /// each hold one box and wait for a second can block each other forever.
+    pub async fn acquire(range: BoxRange) -> Self {
+        loop {
+            // Created before looking for a box, so a release in between is
+            // not missed.
+            let notified = released().notified();
+            {
+                let mut in_use = IN_USE.lock().unwrap();
+                if let Some(id) =
+                    (range.first..range.first + range.count).find(|id| !in_use.contains(id))
+                {
+                    in_use.insert(id);
+                    return BoxLease { id };
+                }
+            }
+            notified.await;
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for BoxLease {
+    fn drop(&mut self) {
+        IN_USE.lock().unwrap().remove(&self.id);
+        released().notify_waiters();
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::config::{self, BoxRange};
 use crate::errors::{GraderError, GraderResult};
 use crate::instance;
 use crate::utils::tests::{compile_cpp, get_example_dir, get_tmp_path, TempDir};
@@ -21,7 +22,8 @@ async fn should_complete_initialize_instance() -> GraderResult<()> {
         bin_path: tmp_dir.0.join("bin"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
         output_path: tmp_dir.0.join("output.txt"),
-        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp")
+        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp"),
+        config: config::get()?
     };
 
     instance.init().await?;
@@ -42,7 +44,8 @@ async fn should_error_if_input_path_is_wrong() -> GraderResult<()> {
         memory_limit: 512000,
         bin_path: tmp_dir.0.join("bin"),
         input_path: base_dir.join("input_wrong_path"),
-        runner_path: base_dir.join("run_cpp")
+        runner_path: base_dir.join("run_cpp"),
+        config: config::get()?
     };
 
     let _init_result = instance.init().await;
@@ -70,7 +73,8 @@ async fn should_error_if_output_path_is_wrong() -> GraderResult<()> {
         memory_limit: 512000,
         bin_path: tmp_dir.0.join("bin_wrong_path"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
-        runner_path: base_dir.join("run_cpp")
+        runner_path: base_dir.join("run_cpp"),
+        config: config::get()?
     };
 
     let _init_result = instance.init().await;
@@ -98,7 +102,8 @@ async fn should_error_if_runner_path_is_wrong() -> GraderResult<()> {
         memory_limit: 512000,
         bin_path: tmp_dir.0.join("bin"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
-        runner_path: base_dir.join("run_cpp_wrong_path")
+        runner_path: base_dir.join("run_cpp_wrong_path"),
+        config: config::get()?
     };
 
     let _init_result = instance.init().await;
@@ -281,7 +286,8 @@ async fn should_get_ok() -> GraderResult<()> {
         bin_path: tmp_dir.0.join("bin"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
         output_path: tmp_dir.0.join("output.txt"),
-        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp")
+        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp"),
+        config: config::get()?
     };
 
     instance.init().await?;
@@ -305,7 +311,8 @@ async fn should_get_tle() -> GraderResult<()> {
         memory_limit: 512000,
         bin_path: tmp_dir.0.join("bin"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
-        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp")
+        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp"),
+        config: config::get()?
     };
 
     instance.init().await?;
@@ -329,7 +336,8 @@ async fn should_get_re() -> GraderResult<()> {
         memory_limit: 512000,
         bin_path: tmp_dir.0.join("bin"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
-        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp")
+        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp"),
+        config: config::get()?
     };
 
     instance.init().await?;
@@ -353,7 +361,8 @@ async fn should_get_mle() -> GraderResult<()> {
         memory_limit: 32,
         bin_path: tmp_dir.0.join("bin"),
         input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
-        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp")
+        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp"),
+        config: config::get()?
     };
 
     instance.init().await?;
@@ -362,3 +371,107 @@ async fn should_get_mle() -> GraderResult<()> {
     assert_eq!(result.status, RunVerdict::VerdictMLE);
     Ok(())
 }
+
+#[test]
+async fn should_redirect_stderr_inside_box() -> GraderResult<()> {
+    dotenv().ok();
+
+    let instance = instance! {
+        time_limit: 1.0,
+        memory_limit: 512000
+    };
+
+    let args = instance.get_run_arguments()?;
+    let redirect = args.iter().position(|arg| arg == "-r").unwrap();
+    assert_eq!(args[redirect + 1], "stderr");
+    let fsize = args.iter().position(|arg| arg == "-f").unwrap();
+    assert_eq!(args[fsize + 1], OUTPUT_LIMIT.to_string());
+    Ok(())
+}
+
+#[test]
+async fn should_capture_stderr() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("should_capture_stderr");
+
+    fs::write(
+        tmp_dir.0.join("stderr.cpp"),
+        "#include <cstdio>\nint main() { fprintf(stderr, \"oops\\n\"); return 1; }\n",
+    )
+    .await?;
+    compile_cpp(&tmp_dir.0, &tmp_dir.0.join("stderr.cpp"));
+
+    let mut instance = instance! {
+        time_limit: 1.0,
+        memory_limit: 512000,
+        bin_path: tmp_dir.0.join("bin"),
+        input_path: get_example_dir().join("tasks").join("a_plus_b").join("testcases").join("1.in"),
+        stderr_path: tmp_dir.0.join("stderr"),
+        runner_path: get_example_dir().join("scripts").join("runner_scripts").join("cpp"),
+        config: config::get()?
+    };
+
+    instance.init().await?;
+    let result = instance.run().await?;
+
+    assert_eq!(result.status, RunVerdict::VerdictRE);
+    assert_eq!(
+        fs::read_to_string(tmp_dir.0.join("stderr")).await?,
+        "oops\n"
+    );
+    Ok(())
+}
+
+#[test]
+async fn should_limit_compilation() -> GraderResult<()> {
+    dotenv().ok();
+
+    let mut instance = compile::CompileInstance::default();
+    instance.box_id = 7;
+    instance.log_file = get_tmp_path().join("tmp_compile_log_7.txt");
+    instance.work_path = get_tmp_path().join("000007");
+    instance.script_path = get_example_dir()
+        .join("scripts")
+        .join("compile_scripts")
+        .join("cpp");
+    instance.limit = compile::CompileLimit {
+        time_limit: 2.5,
+        memory_limit: 256,
+        output_limit: 8,
+    };
+
+    let args = instance.get_run_arguments()?;
+    let value = |flag: &str| args[args.iter().position(|arg| arg == flag).unwrap() + 1].clone();
+    assert_eq!(value("-t"), "2.5");
+    assert_eq!(value("-f"), "8192");
+    assert!(args.contains(&String::from("--cg-mem=262144")));
+    assert!(args.contains(&String::from("HOME=/box")));
+    assert!(args.contains(&format!(
+        "--dir={}:rw",
+        instance.work_path.to_str().unwrap()
+    )));
+    Ok(())
+}
+
+#[test]
+async fn should_lease_distinct_boxes() {
+    let range = BoxRange {
+        first: 2000,
+        count: 2,
+    };
+    let first = pool::BoxLease::acquire(range).await;
+    let second = pool::BoxLease::acquire(range).await;
+    assert_ne!(first.id(), second.id());
+    assert!((2000..2002).contains(&first.id()));
+    assert!((2000..2002).contains(&second.id()));
+
+    // The range is full, so the third lease waits for a release.
+    let released = second.id();
+    let third = tokio::spawn(pool::BoxLease::acquire(range));
+    tokio::task::yield_now().await;
+    assert!(!third.is_finished());
+    drop(second);
+    let third = third.await.unwrap();
+    assert_eq!(third.id(), released);
+}
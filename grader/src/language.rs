@@ -0,0 +1,252 @@
+//! Languages declared in `scripts/config.yaml`.
+//!
+//! A language needs only an `id` and an `extension`, in which case it is
+//! compiled by `compile_scripts/<id>` and run by `runner_scripts/<id>`. It can
+//! instead declare its steps directly:
+//!
+//! ```yaml
+//! - id: "cpp17"
+//!   extension: "cpp"
+//!   source_name: "main.cpp"
+//!   compile_command: ["/usr/bin/g++", "-std=c++17", "-O2", "{sources}", "-o", "{binary}"]
+//!   run_command: ["{binary}"]
+//!   binary_name: "bin"
+//!   time_multiplier: 1.0
+//!   memory_allowance: 0
+//!   sandbox_dirs: ["/usr/lib/jvm"]
+//!   processes: 64
+//!   compile:
+//!     time_limit: 30.0
+//! ```
+//!
+//! In commands, an argument that is exactly `{source}` is replaced by the first
+//! source file, `{sources}` by every source and compile file, and `{binary}` by
+//! the compiled binary.
+
+use crate::errors::{GraderError, GraderResult};
+use crate::instance::compile::CompileLimit;
+use crate::utils::shell_quote;
+use yaml_rust::Yaml;
+
+const DEFAULT_BINARY_NAME: &str = "bin";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Language {
+    pub id: String,
+    pub extension: String,
+    /// Name of the first source file, `code_0.<extension>` when unset.
+    pub source_name: Option<String>,
+    /// Compile command used instead of `compile_scripts/<id>`.
+    pub compile_command: Option<Vec<String>>,
+    /// Run command used instead of `runner_scripts/<id>`.
+    pub run_command: Option<Vec<String>>,
+    /// Name of the binary written by `compile_command`.
+    pub binary_name: String,
+    /// Factor applied to the task's time limit.
+    pub time_multiplier: f64,
+    /// Megabytes added to the task's memory limit.
+    pub memory_allowance: u64,
+    /// Extra directories mounted read-only when compiling and running.
+    pub sandbox_dirs: Vec<String>,
+    /// Process limit inside the sandbox, 128 when unset.
+    pub processes: Option<u64>,
+    /// Limits of the compile step, defaulting to the top-level `compile` entry.
+    pub compile_limit: CompileLimit,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Language {
+            id: String::new(),
+            extension: String::new(),
+            source_name: None,
+            compile_command: None,
+            run_command: None,
+            binary_name: String::from(DEFAULT_BINARY_NAME),
+            time_multiplier: 1.0,
+            memory_allowance: 0,
+            sandbox_dirs: Vec::new(),
+            processes: None,
+            compile_limit: CompileLimit::default(),
+        }
+    }
+}
+
+impl Language {
+    /// Parse a `language` entry, with `compile_limit` as the limits its own
+    /// `compile` entry does not set.
+    pub fn from_yaml(yaml: &Yaml, compile_limit: CompileLimit) -> GraderResult<Self> {
+        let string = |key: &str| yaml[key].as_str().map(|value| value.to_owned());
+        let command = |key: &str| -> GraderResult<Option<Vec<String>>> {
+            match &yaml[key] {
+                Yaml::BadValue => Ok(None),
+                Yaml::Array(args) if !args.is_empty() => args
+                    .iter()
+                    .map(|arg| {
+                        arg.as_str().map(|arg| arg.to_owned()).ok_or_else(|| {
+                            GraderError::parse_err("language", format!("{key} must list strings"))
+                        })
+                    })
+                    .collect::<GraderResult<Vec<_>>>()
+                    .map(Some),
+                _ => Err(GraderError::parse_err(
+                    "language",
+                    format!("{key} must be a non-empty list"),
+                )),
+            }
+        };
+
+        let default = Language::default();
+        Ok(Language {
+            id: string("id").ok_or_else(|| GraderError::parse_err("language", "missing id"))?,
+            extension: string("extension").unwrap_or_default(),
+            source_name: string("source_name"),
+            compile_command: command("compile_command")?,
+            run_command: command("run_command")?,
+            binary_name: string("binary_name").unwrap_or(default.binary_name),
+            time_multiplier: yaml["time_multiplier"]
+                .as_f64()
+                .or_else(|| yaml["time_multiplier"].as_i64().map(|x| x as f64))
+                .unwrap_or(default.time_multiplier),
+            memory_allowance: yaml["memory_allowance"]
+                .as_i64()
+                .map_or(default.memory_allowance, |x| x as u64),
+            sandbox_dirs: yaml["sandbox_dirs"]
+                .as_vec()
+                .into_iter()
+                .flatten()
+                .filter_map(|dir| dir.as_str().map(|dir| dir.to_owned()))
+                .collect(),
+            processes: yaml["processes"].as_i64().map(|x| x as u64),
+            compile_limit: CompileLimit::from_yaml(&yaml["compile"], compile_limit),
+        })
+    }
+
+    /// A compile script following the `compile_scripts` protocol: it takes the
+    /// working directory followed by the files to compile, writes `compileMsg`
+    /// and prints the return code and the binary path.
+    pub fn compile_script(&self) -> Option<String> {
+        let command = self.compile_command.as_ref()?;
+        let command = command
+            .iter()
+            .map(|arg| match arg.as_str() {
+                "{source}" => String::from("\"$1\""),
+                "{sources}" => String::from("\"$@\""),
+                "{binary}" => format!("\"${{DIR}}\"/{}", shell_quote(&self.binary_name)),
+                _ => shell_quote(arg),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Some(format!(
+            "#!/bin/bash\n\
+             DIR=\"$1\"\n\
+             shift\n\
+             cd \"${{DIR}}\" || exit 1\n\
+             {command} > compileMsg 2>&1\n\
+             echo $?\n\
+             echo \"${{DIR}}\"/{}\n",
+            shell_quote(&self.binary_name)
+        ))
+    }
+
+    /// A runner script executing the binary from inside the box.
+    pub fn runner_script(&self) -> Option<String> {
+        let command = self.run_command.as_ref()?;
+        let command = command
+            .iter()
+            .map(|arg| match arg.as_str() {
+                "{binary}" => format!("./{}", shell_quote(&self.binary_name)),
+                _ => shell_quote(arg),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        Some(format!("#!/bin/bash\nexec {command}\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use yaml_rust::YamlLoader;
+
+    fn parse(yaml: &str) -> GraderResult<Language> {
+        Language::from_yaml(
+            &YamlLoader::load_from_str(yaml).unwrap()[0],
+            CompileLimit::default(),
+        )
+    }
+
+    #[test]
+    fn should_parse_declared_language() -> GraderResult<()> {
+        let language = parse(
+            r#"
+id: "java17"
+extension: "java"
+source_name: "Main.java"
+compile_command: ["javac", "{sources}"]
+run_command: ["java", "-cp", ".", "Main"]
+time_multiplier: 2
+memory_allowance: 256
+sandbox_dirs: ["/usr/lib/jvm"]
+processes: 64
+"#,
+        )?;
+
+        assert_eq!(language.source_name.as_deref(), Some("Main.java"));
+        assert_eq!(language.binary_name, "bin");
+        assert_eq!(language.time_multiplier, 2.0);
+        assert_eq!(language.memory_allowance, 256);
+        assert_eq!(language.sandbox_dirs, vec![String::from("/usr/lib/jvm")]);
+        assert_eq!(language.processes, Some(64));
+        Ok(())
+    }
+
+    #[test]
+    fn should_keep_scripts_without_commands() -> GraderResult<()> {
+        let language = parse("id: \"cpp\"\nextension: \"cpp\"")?;
+
+        assert_eq!(language.compile_script(), None);
+        assert_eq!(language.runner_script(), None);
+        assert!(parse("id: \"cpp\"\ncompile_command: []").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn should_generate_scripts() -> GraderResult<()> {
+        let language = parse(
+            r#"
+id: "cpp17"
+extension: "cpp"
+compile_command: ["/usr/bin/g++", "-DNAME='x'", "{sources}", "-o", "{binary}"]
+run_command: ["{binary}", "--fast"]
+"#,
+        )?;
+
+        let compile_script = language.compile_script().unwrap();
+        assert!(compile_script.contains(
+            "'/usr/bin/g++' '-DNAME='\\''x'\\''' \"$@\" '-o' \"${DIR}\"/'bin' > compileMsg"
+        ));
+        assert!(compile_script.ends_with("echo \"${DIR}\"/'bin'\n"));
+        assert_eq!(
+            language.runner_script().unwrap(),
+            "#!/bin/bash\nexec ./'bin' '--fast'\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn should_load_language_from_config() -> GraderResult<()> {
+        dotenv::dotenv().ok();
+
+        let config = crate::config::get()?;
+        let cpp = config.language("cpp").unwrap();
+        assert_eq!(cpp.extension, "cpp");
+        assert_eq!(cpp.compile_script(), None);
+
+        let cpp17 = config.language("cpp17").unwrap();
+        assert!(cpp17.compile_script().is_some());
+        assert_eq!(config.language("brainfuck"), None);
+        Ok(())
+    }
+}
@@ -1,5 +1,9 @@
+pub mod archive;
+pub mod config;
 pub mod errors;
+pub mod importer;
 pub mod instance;
+pub mod language;
 pub mod submission;
 pub mod utils;
 
@@ -0,0 +1,197 @@
+//! Content-addressed cache of compile results.
+//!
+//! An entry is keyed by the hash of the language, the compile script, the
+//! names and contents of every file passed to it and of every file the task
+//! ships in `compile_files`, such as headers. It keeps the compiled
+//! binary, if any, together with the return code and the compiler message,
+//! so an identical submission skips compilation entirely. Entries are
+//! evicted least recently used first once the cache grows past its size.
+
+use crate::errors::{GraderError, GraderResult};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::fs;
+
+const RESULT_FILE: &str = "result";
+const MESSAGE_FILE: &str = "compileMsg";
+const BINARY_FILE: &str = "bin";
+const LAST_USED_FILE: &str = "last_used";
+
+static STAGING_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedCompile {
+    pub return_code: i32,
+    /// Where the binary was restored, `None` when compilation failed.
+    pub bin_path: Option<PathBuf>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+/// Hash everything that determines the outcome of compiling `files` with
+/// the compile script at `script_path`, next to the `task_files` copied into
+/// the work directory.
+pub async fn cache_key(
+    language: &str,
+    script_path: &Path,
+    files: &[PathBuf],
+    task_files: &[PathBuf],
+) -> GraderResult<String> {
+    let mut hasher = Sha256::new();
+    let mut update = |data: &[u8]| {
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(data);
+    };
+
+    update(language.as_bytes());
+    update(&fs::read(script_path).await?);
+    update(&(files.len() as u64).to_le_bytes());
+    for file in files.iter().chain(task_files) {
+        let name = file.file_name().ok_or(GraderError::invalid_value())?;
+        update(
+            name.to_str()
+                .ok_or(GraderError::invalid_to_str())?
+                .as_bytes(),
+        );
+        update(&fs::read(file).await?);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+impl CompileCache {
+    pub fn new(path: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        CompileCache {
+            path: path.into(),
+            max_bytes,
+        }
+    }
+
+    /// Restore the entry for `key` into `work_path`, writing the binary under
+    /// the name it was compiled to.
+    pub async fn lookup(&self, key: &str, work_path: &Path) -> GraderResult<Option<CachedCompile>> {
+        let entry_path = self.path.join(key);
+        let result = match fs::read_to_string(entry_path.join(RESULT_FILE)).await {
+            Ok(result) => result,
+            Err(_) => return Ok(None),
+        };
+        let mut lines = result.lines();
+        let return_code = lines
+            .next()
+            .ok_or(GraderError::invalid_value())?
+            .parse::<i32>()?;
+        let bin_path = match lines.next() {
+            Some(bin_name) if !bin_name.is_empty() => {
+                let bin_path = work_path.join(bin_name);
+                fs::copy(entry_path.join(BINARY_FILE), &bin_path).await?;
+                Some(bin_path)
+            }
+            _ => None,
+        };
+        let message = fs::read_to_string(entry_path.join(MESSAGE_FILE))
+            .await
+            .unwrap_or_default();
+        fs::write(entry_path.join(LAST_USED_FILE), "").await.ok();
+
+        log::debug!("compile cache hit for {key}");
+        Ok(Some(CachedCompile {
+            return_code,
+            bin_path,
+            message,
+        }))
+    }
+
+    /// Save a compile result under `key`, then evict old entries if the cache
+    /// is over its size.
+    pub async fn store(&self, key: &str, compiled: &CachedCompile) -> GraderResult<()> {
+        let entry_path = self.path.join(key);
+        if entry_path.is_dir() {
+            return Ok(());
+        }
+
+        let staging_path = self.path.join(format!(
+            ".{key}.{}.{}.tmp",
+            std::process::id(),
+            STAGING_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let written = self.write_entry(&staging_path, compiled).await;
+        if written.is_err() || fs::rename(&staging_path, &entry_path).await.is_err() {
+            // Either writing failed or another submission stored the same key first.
+            fs::remove_dir_all(&staging_path).await.ok();
+        }
+        written?;
+
+        self.evict().await
+    }
+
+    async fn write_entry(&self, staging_path: &Path, compiled: &CachedCompile) -> GraderResult<()> {
+        fs::create_dir_all(staging_path).await?;
+        let bin_name = match &compiled.bin_path {
+            Some(bin_path) => {
+                fs::copy(bin_path, staging_path.join(BINARY_FILE)).await?;
+                bin_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .ok_or(GraderError::invalid_to_str())?
+            }
+            None => "",
+        };
+        fs::write(staging_path.join(MESSAGE_FILE), &compiled.message).await?;
+        fs::write(staging_path.join(LAST_USED_FILE), "").await?;
+        fs::write(
+            staging_path.join(RESULT_FILE),
+            format!("{}\n{bin_name}\n", compiled.return_code),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Remove the least recently used entries until the cache fits `max_bytes`.
+    async fn evict(&self) -> GraderResult<()> {
+        let mut entries = Vec::new();
+        let mut total_bytes = 0;
+        let mut directory = fs::read_dir(&self.path).await?;
+        while let Some(entry) = directory.next_entry().await? {
+            // Staging directories of entries still being written.
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let entry_path = entry.path();
+            let last_used = match fs::metadata(entry_path.join(LAST_USED_FILE)).await {
+                Ok(metadata) => metadata.modified()?,
+                Err(_) => continue,
+            };
+            let mut bytes = 0;
+            let mut files = fs::read_dir(&entry_path).await?;
+            while let Some(file) = files.next_entry().await? {
+                bytes += file.metadata().await?.len();
+            }
+            total_bytes += bytes;
+            entries.push((last_used, bytes, entry_path));
+        }
+
+        entries.sort_by_key(|(last_used, _, _)| *last_used);
+        for (_, bytes, entry_path) in entries {
+            if total_bytes <= self.max_bytes {
+                break;
+            }
+            log::debug!("evicting {entry_path:?} from the compile cache");
+            // A concurrent eviction may have removed it already.
+            fs::remove_dir_all(&entry_path).await.ok();
+            total_bytes -= bytes;
+        }
+        Ok(())
+    }
+}
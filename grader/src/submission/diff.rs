@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+const READ_LIMIT: u64 = 1 << 20;
+const CONTEXT_LINES: usize = 2;
+const LINE_LIMIT: usize = 256;
+
+/// Where the contestant's output first differs from the expected output,
+/// comparing lines token by token and ignoring whitespace.
+#[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct OutputDiff {
+    /// 1-based line of the first mismatch.
+    pub line: u64,
+    /// 1-based token of the first mismatch within that line.
+    pub token: u64,
+    /// 1-based line of the first context line below.
+    pub context_line: u64,
+    pub expected: Vec<String>,
+    pub output: Vec<String>,
+}
+
+/// Diff the first megabyte of both files, `None` if no difference is found there.
+pub async fn diff_files(output_path: &Path, sol_path: &Path) -> Option<OutputDiff> {
+    let (output, output_truncated) = read_lines(output_path).await?;
+    let (expected, expected_truncated) = read_lines(sol_path).await?;
+    diff_lines(&output, output_truncated, &expected, expected_truncated)
+}
+
+pub fn diff(output: &str, expected: &str) -> Option<OutputDiff> {
+    diff_lines(
+        &output.lines().collect::<Vec<_>>(),
+        false,
+        &expected.lines().collect::<Vec<_>>(),
+        false,
+    )
+}
+
+fn diff_lines<S: AsRef<str>>(
+    output: &[S],
+    output_truncated: bool,
+    expected: &[S],
+    expected_truncated: bool,
+) -> Option<OutputDiff> {
+    let tokens = |lines: &[S], index: usize| -> Vec<String> {
+        lines.get(index).map_or(Vec::new(), |line| {
+            line.as_ref()
+                .split_whitespace()
+                .map(|token| token.to_owned())
+                .collect()
+        })
+    };
+    let is_blank_from = |lines: &[S], index: usize| {
+        lines
+            .iter()
+            .skip(index)
+            .all(|line| line.as_ref().trim().is_empty())
+    };
+
+    let mut index = 0;
+    loop {
+        let output_ended = index >= output.len();
+        let expected_ended = index >= expected.len();
+        if (output_ended && output_truncated) || (expected_ended && expected_truncated) {
+            return None;
+        }
+        if output_ended && expected_ended {
+            return None;
+        }
+        if (output_ended && is_blank_from(expected, index))
+            || (expected_ended && is_blank_from(output, index))
+        {
+            return None;
+        }
+
+        let output_tokens = tokens(output, index);
+        let expected_tokens = tokens(expected, index);
+        if output_tokens != expected_tokens {
+            let token = output_tokens
+                .iter()
+                .zip(expected_tokens.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let context_start = index.saturating_sub(CONTEXT_LINES);
+            let context = |lines: &[S]| {
+                lines
+                    .iter()
+                    .skip(context_start)
+                    .take(index + CONTEXT_LINES + 1 - context_start)
+                    .map(|line| truncate(line.as_ref()))
+                    .collect()
+            };
+            return Some(OutputDiff {
+                line: index as u64 + 1,
+                token: token as u64 + 1,
+                context_line: context_start as u64 + 1,
+                expected: context(expected),
+                output: context(output),
+            });
+        }
+        index += 1;
+    }
+}
+
+/// Read the complete lines within the first `READ_LIMIT` bytes of `path`,
+/// telling whether the file continues past them.
+async fn read_lines(path: &Path) -> Option<(Vec<String>, bool)> {
+    let file = tokio::fs::File::open(path).await.ok()?;
+    let mut content = Vec::new();
+    file.take(READ_LIMIT + 1)
+        .read_to_end(&mut content)
+        .await
+        .ok()?;
+
+    let truncated = content.len() as u64 > READ_LIMIT;
+    if truncated {
+        let end = content.iter().rposition(|&c| c == b'\n').unwrap_or(0);
+        content.truncate(end);
+    }
+    let lines = String::from_utf8_lossy(&content)
+        .lines()
+        .map(|line| line.to_owned())
+        .collect();
+    Some((lines, truncated))
+}
+
+fn truncate(line: &str) -> String {
+    match line.char_indices().nth(LINE_LIMIT) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_owned(),
+    }
+}
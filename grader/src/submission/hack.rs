@@ -0,0 +1,144 @@
+//! Hacks: custom inputs submitted against another contestant's submission.
+//!
+//! The input is checked by the task's validator, the expected answer comes
+//! from running the task's reference solution on it, and the hacked
+//! submission is then run and judged with the task's checker.
+
+use super::{check_input_size, verdict_status, Submission, SubmissionMessage, CHECKER_TIMEOUT};
+use crate::errors::{GraderError, GraderResult};
+use crate::instance::RunVerdict;
+use crate::submission::result::{HackResult, HackVerdict};
+use crate::submission::source::SourceFile;
+use crate::utils::{join_within, output_with_timeout};
+use futures::sink::{Drain, Sink};
+use std::{path::Path, path::PathBuf, process::Stdio};
+use tokio::{fs, process::Command};
+
+const VALIDATOR_MESSAGE_SIZE: usize = 1024;
+
+impl<T> Submission<T> {
+    /// Hack this submission with `input`.
+    ///
+    /// Fails if the task has no validator or reference solution, if the
+    /// reference solution does not finish normally on the input, or if the
+    /// hacked submission does not compile or cannot be judged. The hack
+    /// succeeds only when the submission fails the input outright; a partially
+    /// correct answer does not count.
+    pub async fn hack(&mut self, input: &[u8]) -> GraderResult<HackResult>
+    where
+        T: Sink<SubmissionMessage> + std::marker::Unpin,
+    {
+        check_input_size(input)?;
+        let mut result = HackResult {
+            submission_id: self.submission_id.clone(),
+            ..Default::default()
+        };
+
+        let input_path = self.tmp_path.join("hack_input");
+        fs::write(&input_path, input).await?;
+        if let Some(message) = self.validate(&input_path).await? {
+            result.verdict = HackVerdict::InvalidInput;
+            result.validator_message = message;
+            return Ok(result);
+        }
+
+        let mut reference = self.reference_solution().await?;
+        if !reference.compile().await? {
+            return Err(GraderError::invalid_task(format!(
+                "reference solution does not compile: {}",
+                reference.compile_message
+            )));
+        }
+        let sol_path = reference.tmp_path.join("hack_answer");
+        let reference_result = reference.execute_once(&input_path, &sol_path).await?;
+        if reference_result != RunVerdict::VerdictOK {
+            return Err(GraderError::invalid_task(format!(
+                "reference solution finished with {}",
+                verdict_status(&reference_result)
+            )));
+        }
+
+        if self.bin_path == PathBuf::new() && !self.compile().await? {
+            return Err(GraderError::compilation_failed(format!(
+                "the hacked submission does not compile: {}",
+                self.compile_message
+            )));
+        }
+        let output_path = self.tmp_path.join("hack_output");
+        let run_result = self
+            .judge_input(&input_path, &output_path, &sol_path)
+            .await?;
+
+        result.verdict = match run_result.status.as_str() {
+            "Correct" | "Partially Correct" => HackVerdict::Unsuccessful,
+            "Incorrect"
+            | "Time Limit Exceeded"
+            | "Memory Limit Exceeded"
+            | "Runtime Error"
+            | "Signal Error" => HackVerdict::Successful,
+            status => {
+                return Err(GraderError::judge_error(format!(
+                    "the hacked submission was judged {status}: {}",
+                    run_result.message
+                )))
+            }
+        };
+        result.run_result = Some(run_result);
+        Ok(result)
+    }
+
+    /// Run the task's validator on the input, returning its output if it
+    /// rejects the input.
+    async fn validate(&self, input_path: &Path) -> GraderResult<Option<String>> {
+        let validator = match &self.task_manifest.validator {
+            Some(file) => join_within(&self.task_path, file)?,
+            None => self.task_path.join("validator"),
+        };
+        if !validator.is_file() {
+            return Err(GraderError::invalid_task("task has no validator"));
+        }
+
+        let input = std::fs::File::open(input_path)?;
+        let output = output_with_timeout(
+            Command::new(validator).stdin(Stdio::from(input)),
+            CHECKER_TIMEOUT,
+        )
+        .await?;
+        if output.status.success() {
+            return Ok(None);
+        }
+        let mut message = output.stdout;
+        message.extend(output.stderr);
+        message.truncate(VALIDATOR_MESSAGE_SIZE);
+        Ok(Some(
+            String::from_utf8_lossy(&message).trim_end().to_owned(),
+        ))
+    }
+
+    /// Load the task's reference solution as a submission of its own.
+    async fn reference_solution(&self) -> GraderResult<Submission<Drain<SubmissionMessage>>> {
+        let (language, files) = self
+            .task_manifest
+            .reference_solution
+            .as_ref()
+            .and_then(|solutions| solutions.iter().next())
+            .ok_or(GraderError::invalid_task("task has no reference solution"))?;
+
+        let solutions_path = self.task_path.join("solutions");
+        let mut code = Vec::new();
+        for file in files {
+            let content = fs::read(join_within(&solutions_path, file)?).await?;
+            code.push(SourceFile::named(file, content));
+        }
+
+        Submission::try_from_config(
+            self.config.clone(),
+            &self.task_id,
+            format!("{}_reference", self.submission_id),
+            language,
+            &code,
+            futures::sink::drain(),
+        )
+        .await
+    }
+}
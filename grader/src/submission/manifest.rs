@@ -1,23 +1,77 @@
 use crate::errors::{GraderError, GraderResult};
+use crate::submission::scoring::Scoring;
 use crate::utils::load_yaml;
-use std::{collections::BTreeMap, path::PathBuf};
+use std::{collections::BTreeMap, convert::TryFrom, fs, path::Path, path::PathBuf};
+use yaml_rust::{yaml::Hash, Yaml, YamlEmitter};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, PartialEq)]
 pub struct Manifest {
     pub task_id: String,
     pub output_only: bool,
     pub time_limit: Option<f64>,
     pub memory_limit: Option<u64>,
     pub limit: Option<BTreeMap<String, (f64, u64)>>,
+    /// Languages accepted by the task, every configured language when unset.
+    pub languages: Option<Vec<String>>,
+    /// Total size of the submitted sources in kilobytes, replacing the
+    /// configured limit.
+    pub max_source_size: Option<u64>,
+    /// Number of submitted source files, replacing the configured limit.
+    pub max_source_files: Option<u64>,
+    /// Names of the files a submission must consist of, per language.
+    pub source_files: Option<BTreeMap<String, Vec<String>>>,
     pub compile_files: Option<BTreeMap<String, Vec<String>>>,
     pub checker: Option<String>,
     pub grouper: Option<String>,
-    pub groups: Vec<(f64, u64)>,
+    /// Rescales the checker's score of every test.
+    pub scoring: Option<Scoring>,
+    /// Executable in the task directory that accepts a hack's input on stdin
+    /// by exiting with 0, `validator` when unset.
+    pub validator: Option<String>,
+    /// Files of the reference solution in the task's `solutions` directory,
+    /// keyed by its language. Only the first language is used.
+    pub reference_solution: Option<BTreeMap<String, Vec<String>>>,
+    pub solution_extension: Option<String>,
+    /// Attach a diff against the expected output to incorrect sample tests.
+    pub output_diff: bool,
+    pub groups: Vec<Group>,
+}
+
+/// How the testcases of a group are found in the task's `testcases` directory.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TestSelector {
+    /// The next `n` testcases named `{index}.in`, numbered across all groups.
+    Count(u64),
+    /// Input files listed explicitly, relative to the `testcases` directory.
+    Files(Vec<String>),
+    /// Input files matching a wildcard pattern such as `sub2/*.in`.
+    Pattern(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Group {
+    pub full_score: f64,
+    pub tests: TestSelector,
+    pub solution_extension: Option<String>,
+    /// Sample tests report the contestant's output, the expected output and
+    /// the checker message back to the contestant.
+    pub sample: bool,
+}
+
+impl Group {
+    pub fn count(full_score: f64, tests: u64) -> Self {
+        Group {
+            full_score,
+            tests: TestSelector::Count(tests),
+            solution_extension: None,
+            sample: false,
+        }
+    }
 }
 
 impl Manifest {
     pub fn from(path: PathBuf) -> GraderResult<Self> {
-        let yaml = load_yaml(path);
+        let yaml = load_yaml(&path)?;
         Ok(Manifest {
             task_id: yaml["task_id"]
                 .as_str()
@@ -53,55 +107,256 @@ impl Manifest {
                         .collect::<GraderResult<BTreeMap<_, _>>>()
                 })
                 .transpose()?,
-            compile_files: yaml["compile_files"]
-                .as_hash()
-                .map(|compile_files| {
-                    compile_files
+            languages: yaml["languages"]
+                .as_vec()
+                .map(|languages| {
+                    languages
                         .iter()
-                        .map(|(language, files)| -> GraderResult<(String, Vec<String>)> {
-                            Ok((
-                                language
-                                    .as_str()
-                                    .ok_or(GraderError::invalid_value())?
-                                    .to_owned(),
-                                files
-                                    .as_vec()
-                                    .ok_or(GraderError::invalid_value())?
-                                    .iter()
-                                    .map(|file| {
-                                        Ok(file
-                                            .as_str()
-                                            .ok_or(GraderError::invalid_value())?
-                                            .to_owned())
-                                    })
-                                    .collect::<GraderResult<Vec<_>>>()?,
-                            ))
+                        .map(|language| {
+                            Ok(language
+                                .as_str()
+                                .ok_or(GraderError::invalid_value())?
+                                .to_owned())
                         })
-                        .collect()
+                        .collect::<GraderResult<Vec<_>>>()
                 })
                 .transpose()?,
+            max_source_size: non_negative(&yaml, "max_source_size")?,
+            max_source_files: non_negative(&yaml, "max_source_files")?,
+            source_files: parse_file_lists(&yaml["source_files"])?,
+            compile_files: parse_file_lists(&yaml["compile_files"])?,
             checker: yaml["checker"].as_str().map(|checker| checker.to_owned()),
             grouper: yaml["grouper"].as_str().map(|grouper| grouper.to_owned()),
+            scoring: Scoring::from_yaml(&yaml["scoring"])?,
+            validator: yaml["validator"]
+                .as_str()
+                .map(|validator| validator.to_owned()),
+            reference_solution: parse_file_lists(&yaml["reference_solution"])?,
+            solution_extension: yaml["solution_extension"]
+                .as_str()
+                .map(|extension| extension.to_owned()),
+            output_diff: yaml["output_diff"].as_bool().unwrap_or(false),
             groups: yaml["groups"]
                 .as_vec()
                 .map(|groups| {
                     groups
                         .iter()
                         .map(|group| {
-                            Ok((
-                                group["full_score"]
+                            Ok(Group {
+                                full_score: group["full_score"]
                                     .as_f64()
                                     .or_else(|| group["full_score"].as_i64().map(|x| x as f64))
                                     .ok_or(GraderError::invalid_value())?,
-                                group["tests"]
-                                    .as_i64()
-                                    .ok_or(GraderError::invalid_value())?
-                                    as u64,
-                            ))
+                                tests: match (&group["tests"], group["pattern"].as_str()) {
+                                    (Yaml::Integer(tests), _) => TestSelector::Count(
+                                        u64::try_from(*tests).map_err(|_| {
+                                            GraderError::invalid_task("tests must not be negative")
+                                        })?,
+                                    ),
+                                    (Yaml::Array(files), _) => TestSelector::Files(
+                                        files
+                                            .iter()
+                                            .map(|file| {
+                                                Ok(file
+                                                    .as_str()
+                                                    .ok_or(GraderError::invalid_value())?
+                                                    .to_owned())
+                                            })
+                                            .collect::<GraderResult<Vec<_>>>()?,
+                                    ),
+                                    (_, Some(pattern)) => TestSelector::Pattern(pattern.to_owned()),
+                                    _ => return Err(GraderError::invalid_value()),
+                                },
+                                solution_extension: group["solution_extension"]
+                                    .as_str()
+                                    .map(|extension| extension.to_owned()),
+                                sample: group["sample"].as_bool().unwrap_or(false),
+                            })
                         })
                         .collect::<GraderResult<Vec<_>>>()
                 })
                 .ok_or(GraderError::invalid_value())??,
         })
     }
+
+    pub fn to_yaml(&self) -> Yaml {
+        fn key(name: &str) -> Yaml {
+            Yaml::String(name.to_owned())
+        }
+
+        let mut yaml = Hash::new();
+        yaml.insert(key("task_id"), Yaml::String(self.task_id.clone()));
+        if self.output_only {
+            yaml.insert(key("output_only"), Yaml::Boolean(true));
+        }
+        if let Some(time_limit) = self.time_limit {
+            yaml.insert(key("time_limit"), Yaml::Real(format!("{time_limit:?}")));
+        }
+        if let Some(memory_limit) = self.memory_limit {
+            yaml.insert(key("memory_limit"), Yaml::Integer(memory_limit as i64));
+        }
+        if let Some(limit) = &self.limit {
+            let limit = limit
+                .iter()
+                .map(|(language, (time_limit, memory_limit))| {
+                    let mut value = Hash::new();
+                    value.insert(key("time_limit"), Yaml::Real(format!("{time_limit:?}")));
+                    value.insert(key("memory_limit"), Yaml::Integer(*memory_limit as i64));
+                    (Yaml::String(language.clone()), Yaml::Hash(value))
+                })
+                .collect();
+            yaml.insert(key("limit"), Yaml::Hash(limit));
+        }
+        if let Some(languages) = &self.languages {
+            yaml.insert(
+                key("languages"),
+                Yaml::Array(languages.iter().cloned().map(Yaml::String).collect()),
+            );
+        }
+        if let Some(max_source_size) = self.max_source_size {
+            yaml.insert(
+                key("max_source_size"),
+                Yaml::Integer(max_source_size as i64),
+            );
+        }
+        if let Some(max_source_files) = self.max_source_files {
+            yaml.insert(
+                key("max_source_files"),
+                Yaml::Integer(max_source_files as i64),
+            );
+        }
+        if let Some(source_files) = &self.source_files {
+            yaml.insert(key("source_files"), file_lists_to_yaml(source_files));
+        }
+        if let Some(compile_files) = &self.compile_files {
+            yaml.insert(key("compile_files"), file_lists_to_yaml(compile_files));
+        }
+        if let Some(checker) = &self.checker {
+            yaml.insert(key("checker"), Yaml::String(checker.clone()));
+        }
+        if let Some(grouper) = &self.grouper {
+            yaml.insert(key("grouper"), Yaml::String(grouper.clone()));
+        }
+        if let Some(scoring) = &self.scoring {
+            yaml.insert(key("scoring"), scoring.to_yaml());
+        }
+        if let Some(validator) = &self.validator {
+            yaml.insert(key("validator"), Yaml::String(validator.clone()));
+        }
+        if let Some(reference_solution) = &self.reference_solution {
+            yaml.insert(
+                key("reference_solution"),
+                file_lists_to_yaml(reference_solution),
+            );
+        }
+        if let Some(extension) = &self.solution_extension {
+            yaml.insert(key("solution_extension"), Yaml::String(extension.clone()));
+        }
+        if self.output_diff {
+            yaml.insert(key("output_diff"), Yaml::Boolean(true));
+        }
+        yaml.insert(
+            key("groups"),
+            Yaml::Array(
+                self.groups
+                    .iter()
+                    .map(|group| {
+                        let mut value = Hash::new();
+                        value.insert(
+                            key("full_score"),
+                            Yaml::Real(format!("{:?}", group.full_score)),
+                        );
+                        match &group.tests {
+                            TestSelector::Count(tests) => {
+                                value.insert(key("tests"), Yaml::Integer(*tests as i64));
+                            }
+                            TestSelector::Files(files) => {
+                                value.insert(
+                                    key("tests"),
+                                    Yaml::Array(files.iter().cloned().map(Yaml::String).collect()),
+                                );
+                            }
+                            TestSelector::Pattern(pattern) => {
+                                value.insert(key("pattern"), Yaml::String(pattern.clone()));
+                            }
+                        }
+                        if group.sample {
+                            value.insert(key("sample"), Yaml::Boolean(true));
+                        }
+                        if let Some(extension) = &group.solution_extension {
+                            value
+                                .insert(key("solution_extension"), Yaml::String(extension.clone()));
+                        }
+                        Yaml::Hash(value)
+                    })
+                    .collect(),
+            ),
+        );
+        Yaml::Hash(yaml)
+    }
+
+    pub fn save(&self, path: &Path) -> GraderResult<()> {
+        let mut content = String::new();
+        YamlEmitter::new(&mut content)
+            .dump(&self.to_yaml())
+            .map_err(|e| GraderError::invalid_io(format!("{e:?}")))?;
+        content.push('\n');
+        fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+/// Parse an optional limit, which must not be negative.
+fn non_negative(yaml: &Yaml, key: &str) -> GraderResult<Option<u64>> {
+    yaml[key]
+        .as_i64()
+        .map(|limit| {
+            u64::try_from(limit)
+                .map_err(|_| GraderError::invalid_task(format!("{key} must not be negative")))
+        })
+        .transpose()
+}
+
+/// Parse a map from language to a list of file names.
+fn parse_file_lists(yaml: &Yaml) -> GraderResult<Option<BTreeMap<String, Vec<String>>>> {
+    yaml.as_hash()
+        .map(|lists| {
+            lists
+                .iter()
+                .map(|(language, files)| -> GraderResult<(String, Vec<String>)> {
+                    Ok((
+                        language
+                            .as_str()
+                            .ok_or(GraderError::invalid_value())?
+                            .to_owned(),
+                        files
+                            .as_vec()
+                            .ok_or(GraderError::invalid_value())?
+                            .iter()
+                            .map(|file| {
+                                Ok(file
+                                    .as_str()
+                                    .ok_or(GraderError::invalid_value())?
+                                    .to_owned())
+                            })
+                            .collect::<GraderResult<Vec<_>>>()?,
+                    ))
+                })
+                .collect()
+        })
+        .transpose()
+}
+
+fn file_lists_to_yaml(lists: &BTreeMap<String, Vec<String>>) -> Yaml {
+    Yaml::Hash(
+        lists
+            .iter()
+            .map(|(language, files)| {
+                (
+                    Yaml::String(language.clone()),
+                    Yaml::Array(files.iter().cloned().map(Yaml::String).collect()),
+                )
+            })
+            .collect(),
+    )
 }
@@ -1,27 +1,55 @@
+use crate::archive::ExtractLimit;
+use crate::config::{self, GraderConfig, RerunTime};
 use crate::errors::{GraderError, GraderResult};
 use crate::instance;
-use crate::instance::{Instance, RunVerdict};
+use crate::instance::{compile::CompileInstance, pool::BoxLease, Instance, RunVerdict};
+use crate::language::Language;
 use crate::submission::result::*;
-use crate::utils::{get_base_path, get_code_extension, get_env, get_message};
+use crate::utils::{join_within, output_with_timeout, read_excerpt};
+use compile_cache::{CachedCompile, CompileCache};
 use futures::sink::{Sink, SinkExt};
 use manifest::Manifest;
-use std::{io::Write, path::Path, path::PathBuf, process::Command};
-use tokio::fs;
+use source::SourceFile;
+use std::{
+    io::Write, os::unix::fs::PermissionsExt, path::Path, path::PathBuf, sync::Arc, time::Duration,
+};
+use testcase::TestGroup;
+use tokio::{fs, process::Command};
 
+pub mod compile_cache;
+pub mod diff;
+pub mod hack;
 pub mod manifest;
 pub mod result;
+pub mod scoring;
+pub mod source;
+pub mod stress;
+pub mod testcase;
 
 #[cfg(test)]
 mod tests;
 
+const FEEDBACK_EXCERPT_SIZE: usize = 1024;
+const STDERR_EXCERPT_SIZE: usize = 4096;
+const COMPILE_MESSAGE_SIZE: usize = 16384;
+const TEST_RUN_OUTPUT_SIZE: usize = 64 * 1024;
+/// Largest input accepted for a test run or a hack.
+pub const CUSTOM_INPUT_SIZE: usize = 1024 * 1024;
+const CHECKER_TIMEOUT: Duration = Duration::from_secs(60);
+const GROUPER_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Default)]
 pub enum SubmissionStatus {
     #[default]
     Initialized,
     TaskNotFound,
+    LanguageNotSupported,
+    SourceLimitExceeded,
+    InvalidSourceFiles,
     Compiling,
     Compiled,
     CompilationError(String),
+    CompilationTimeout,
     Running(u64),
     Done(SubmissionResult),
 }
@@ -29,7 +57,7 @@ pub enum SubmissionStatus {
 #[derive(Debug)]
 pub enum SubmissionMessage {
     Status(SubmissionStatus),
-    RunResult(RunResult),
+    RunResult(Box<RunResult>),
     GroupResult(GroupResult),
 }
 impl Default for SubmissionMessage {
@@ -43,12 +71,24 @@ pub struct Submission<T> {
     pub task_id: String,
     pub submission_id: String,
     pub language: String,
+    pub language_config: Language,
     pub code_path: Vec<PathBuf>,
+    /// Files of the task's `compile_files` directory copied next to the code.
+    pub task_files: Vec<PathBuf>,
     pub task_manifest: Manifest,
+    pub test_groups: Vec<TestGroup>,
     pub tmp_path: PathBuf,
     pub task_path: PathBuf,
     pub bin_path: PathBuf,
+    /// Diagnostics of the last compilation.
+    pub compile_message: String,
     pub message_handler: T,
+    /// Locale of the default verdict messages, the configured default when
+    /// unset.
+    pub locale: Option<String>,
+    /// Snapshot of the grader configuration taken when the submission was
+    /// created.
+    pub config: Arc<GraderConfig>,
 }
 
 impl<T> std::fmt::Display for Submission<T> {
@@ -86,12 +126,85 @@ impl<T> std::fmt::Debug for Submission<T> {
 }
 
 impl<T> Submission<T> {
+    /// Create a submission with the current grader configuration.
     pub async fn try_from(
         task_id: impl ToString,
         submission_id: impl ToString,
         language: impl ToString,
-        code: &[String],
+        code: &[impl Clone + Into<SourceFile>],
+        message_handler: T,
+    ) -> GraderResult<Self>
+    where
+        T: Sink<SubmissionMessage> + std::marker::Unpin,
+    {
+        Self::try_from_config(
+            config::get()?,
+            task_id,
+            submission_id,
+            language,
+            code,
+            message_handler,
+        )
+        .await
+    }
+
+    pub async fn try_from_config(
+        config: Arc<GraderConfig>,
+        task_id: impl ToString,
+        submission_id: impl ToString,
+        language: impl ToString,
+        code: &[impl Clone + Into<SourceFile>],
+        message_handler: T,
+    ) -> GraderResult<Self>
+    where
+        T: Sink<SubmissionMessage> + std::marker::Unpin,
+    {
+        Self::create(
+            config,
+            task_id,
+            submission_id,
+            language,
+            code,
+            message_handler,
+            false,
+        )
+        .await
+    }
+
+    /// Create a helper program of a task, such as the generator or the
+    /// brute-force solution of a stress test. It runs with the task's limits
+    /// but ignores the task's `languages`, `source_files` and `compile_files`.
+    pub async fn try_helper_from_config(
+        config: Arc<GraderConfig>,
+        task_id: impl ToString,
+        submission_id: impl ToString,
+        language: impl ToString,
+        code: &[impl Clone + Into<SourceFile>],
+        message_handler: T,
+    ) -> GraderResult<Self>
+    where
+        T: Sink<SubmissionMessage> + std::marker::Unpin,
+    {
+        Self::create(
+            config,
+            task_id,
+            submission_id,
+            language,
+            code,
+            message_handler,
+            true,
+        )
+        .await
+    }
+
+    async fn create(
+        config: Arc<GraderConfig>,
+        task_id: impl ToString,
+        submission_id: impl ToString,
+        language: impl ToString,
+        code: &[impl Clone + Into<SourceFile>],
         mut message_handler: T,
+        helper: bool,
     ) -> GraderResult<Self>
     where
         T: Sink<SubmissionMessage> + std::marker::Unpin,
@@ -99,11 +212,9 @@ impl<T> Submission<T> {
         let task_id = task_id.to_string();
         let submission_id = submission_id.to_string();
         let language = language.to_string();
-        let tmp_path = PathBuf::from(get_env("TEMPORARY_PATH")).join(&submission_id);
-        fs::remove_dir_all(&tmp_path).await.ok();
-        fs::create_dir(&tmp_path).await?;
-        let extension = get_code_extension(&language);
-        let task_path = get_base_path().join("tasks").join(&task_id);
+        let code: Vec<SourceFile> = code.iter().cloned().map(Into::into).collect();
+        let task_path = join_within(&config.tasks_path(), &task_id)?;
+        let tmp_path = join_within(&config.submissions_path(), &submission_id)?;
 
         if !task_path.is_dir() {
             _ = message_handler
@@ -112,37 +223,191 @@ impl<T> Submission<T> {
             return Err(GraderError::task_not_found());
         }
 
-        if task_path.join("compile_files").is_dir() {
+        let mut task_manifest = Manifest::from(task_path.join("manifest.yaml"))?;
+        if helper {
+            task_manifest.languages = None;
+            task_manifest.source_files = None;
+            task_manifest.compile_files = None;
+        }
+        let allowed = task_manifest
+            .languages
+            .as_ref()
+            .is_none_or(|languages| languages.contains(&language));
+        let language_config = match config.language(&language) {
+            Some(language_config) if allowed => language_config.clone(),
+            _ => {
+                _ = message_handler
+                    .send(SubmissionMessage::Status(
+                        SubmissionStatus::LanguageNotSupported,
+                    ))
+                    .await;
+                return Err(GraderError::language_not_supported(language));
+            }
+        };
+
+        let max_size = task_manifest
+            .max_source_size
+            .unwrap_or(config.source_limit.max_size);
+        let max_files = task_manifest
+            .max_source_files
+            .unwrap_or(config.source_limit.max_files);
+        let size: usize = code.iter().map(|file| file.content.len()).sum();
+        let exceeded = if code.len() as u64 > max_files {
+            Some(format!(
+                "{} files submitted, at most {max_files} allowed",
+                code.len()
+            ))
+        } else if size as u64 > max_size.saturating_mul(1024) {
+            Some(format!(
+                "{size} bytes submitted, at most {max_size} KB allowed"
+            ))
+        } else {
+            None
+        };
+        if let Some(msg) = exceeded {
+            _ = message_handler
+                .send(SubmissionMessage::Status(
+                    SubmissionStatus::SourceLimitExceeded,
+                ))
+                .await;
+            return Err(GraderError::source_limit_exceeded(msg));
+        }
+
+        let mut compile_files = Vec::new();
+        if !helper && task_path.join("compile_files").is_dir() {
             let mut entries = fs::read_dir(task_path.join("compile_files")).await?;
             while let Some(entry) = entries.next_entry().await? {
-                let path = entry;
-                fs::copy(&path.path(), tmp_path.join(&path.file_name())).await?;
+                compile_files.push(entry.path());
+            }
+        }
+        compile_files.sort();
+        let reserved = compile_files
+            .iter()
+            .filter_map(|path| Some(path.file_name()?.to_str()?.to_owned()))
+            .collect::<Vec<_>>();
+        let (archives, files): (Vec<_>, Vec<_>) =
+            code.into_iter().partition(|file| file.archive.is_some());
+        let expected = task_manifest
+            .source_files
+            .as_ref()
+            .and_then(|source_files| source_files.get(&language));
+        // With archives, the expected files are only known once unpacked.
+        let unpacks = !archives.is_empty();
+        let file_names = match source::file_names(
+            &files,
+            &language_config,
+            expected.filter(|_| !unpacks),
+            &reserved,
+        ) {
+            Ok(file_names) => file_names,
+            Err(e) => {
+                _ = message_handler
+                    .send(SubmissionMessage::Status(
+                        SubmissionStatus::InvalidSourceFiles,
+                    ))
+                    .await;
+                return Err(e);
             }
+        };
+
+        fs::remove_dir_all(&tmp_path).await.ok();
+        fs::create_dir_all(config.submissions_path()).await?;
+        fs::create_dir(&tmp_path).await?;
+
+        let mut task_files = Vec::new();
+        for path in &compile_files {
+            let file_name = path.file_name().ok_or(GraderError::invalid_value())?;
+            fs::copy(path, tmp_path.join(file_name)).await?;
+            task_files.push(tmp_path.join(file_name));
         }
+        let testcases_path =
+            testcase::prepare(&task_id, &task_path, &config.temporary_path).await?;
+        let test_groups = testcase::discover(&task_manifest, &testcases_path)?;
+
+        let mut code_path = files
+            .iter()
+            .zip(file_names)
+            .map(|(file, file_name)| {
+                let code_path = tmp_path.join(file_name);
+                let mut output = std::fs::File::create(&code_path)?;
+                output.write_all(&file.content)?;
+
+                Ok(code_path)
+            })
+            .collect::<GraderResult<Vec<_>>>()?;
+
+        // Archives share what is left of the source limits once unpacked.
+        let mut limit = ExtractLimit {
+            max_bytes: max_size
+                .saturating_mul(1024)
+                .saturating_sub(files.iter().map(|file| file.content.len() as u64).sum()),
+            max_entries: max_files.saturating_sub(files.len() as u64),
+        };
+        for file in archives {
+            let kind = file.archive.ok_or(GraderError::invalid_value())?;
+            let work_path = tmp_path.clone();
+            let unpacked = tokio::task::spawn_blocking(move || {
+                source::unpack(&file.content, kind, &work_path, limit)
+            })
+            .await?;
+            let unpacked = match unpacked {
+                Ok(unpacked) => unpacked,
+                Err(e) => {
+                    _ = message_handler
+                        .send(SubmissionMessage::Status(
+                            SubmissionStatus::InvalidSourceFiles,
+                        ))
+                        .await;
+                    fs::remove_dir_all(&tmp_path).await.ok();
+                    return Err(e);
+                }
+            };
+            for path in &unpacked {
+                limit.max_bytes = limit
+                    .max_bytes
+                    .saturating_sub(fs::metadata(path).await?.len());
+            }
+            limit.max_entries = limit.max_entries.saturating_sub(unpacked.len() as u64);
+            code_path.extend(unpacked);
+        }
+        if let (Some(expected), true) = (expected, unpacks) {
+            if let Err(e) = source::check_expected(&code_path, &tmp_path, expected) {
+                _ = message_handler
+                    .send(SubmissionMessage::Status(
+                        SubmissionStatus::InvalidSourceFiles,
+                    ))
+                    .await;
+                fs::remove_dir_all(&tmp_path).await.ok();
+                return Err(e);
+            }
+        }
+
         Ok(Submission {
             task_id,
             submission_id,
             language,
-            code_path: code
-                .iter()
-                .enumerate()
-                .map(|(idx, val)| {
-                    let code_path =
-                        tmp_path.join(format!("code_{}.{}", &idx.to_string(), &extension));
-                    let mut file = std::fs::File::create(&code_path)?;
-                    file.write_all(val.as_bytes())?;
-
-                    Ok(code_path)
-                })
-                .collect::<GraderResult<Vec<_>>>()?,
-            task_manifest: Manifest::from(task_path.join("manifest.yaml"))?,
+            language_config,
+            code_path,
+            task_files,
+            task_manifest,
+            test_groups,
             tmp_path,
             task_path,
             bin_path: PathBuf::new(),
+            compile_message: String::new(),
             message_handler,
+            locale: None,
+            config,
         })
     }
 
+    /// Use `locale` for the default verdict messages, or the configured
+    /// default locale when `None`.
+    pub fn with_locale(mut self, locale: Option<String>) -> Self {
+        self.locale = locale;
+        self
+    }
+
     pub async fn compile(&mut self) -> GraderResult<bool>
     where
         T: Sink<SubmissionMessage> + std::marker::Unpin,
@@ -152,72 +417,429 @@ impl<T> Submission<T> {
             .send(SubmissionMessage::Status(SubmissionStatus::Compiling))
             .await;
 
-        let compiler_path = get_base_path()
-            .join("scripts")
-            .join("compile_scripts")
-            .join(&self.language);
-
-        let mut args = vec![&self.tmp_path];
-        self.code_path.iter().for_each(|path| {
-            args.push(path);
-        });
+        let compiler_path = match self.language_config.compile_script() {
+            Some(script) => {
+                let script_path = self.tmp_path.join("compile_script");
+                write_script(&script_path, &script).await?;
+                script_path
+            }
+            None => self
+                .config
+                .scripts_path()
+                .join("compile_scripts")
+                .join(&self.language),
+        };
 
-        let mut tmp_compile_files = vec![];
+        let mut args = self.code_path.clone();
 
         if let Some(compile_files) = &self.task_manifest.compile_files {
             for compile_file in compile_files
                 .get(&self.language)
                 .ok_or(GraderError::invalid_index())?
             {
-                tmp_compile_files.push(self.tmp_path.join(compile_file));
+                args.push(join_within(&self.tmp_path, compile_file)?);
             }
         }
 
-        tmp_compile_files.iter().for_each(|path| {
-            args.push(path);
-        });
+        let cache_size = self.config.compile_cache_size;
+        let cache = CompileCache::new(
+            self.config.temporary_path.join("compile_cache"),
+            cache_size * 1024 * 1024,
+        );
+        let cache_key = if cache_size > 0 {
+            Some(
+                compile_cache::cache_key(&self.language, &compiler_path, &args, &self.task_files)
+                    .await?,
+            )
+        } else {
+            None
+        };
+        if let Some(key) = &cache_key {
+            match cache.lookup(key, &self.tmp_path).await {
+                Ok(Some(cached)) => return Ok(self.finish_compile(cached).await),
+                Ok(None) => (),
+                Err(e) => log::warn!("unable to read compile cache entry {key}: {e}"),
+            }
+        }
 
         log::debug!("compiler path: {compiler_path:?} args: {args:?}");
-        let compile_output = Command::new(compiler_path).args(args).output()?;
+        let compile_limit = self.language_config.compile_limit;
+        let box_lease = self.lease_box().await;
+        let mut compile_instance = CompileInstance::default();
+        compile_instance.box_id = box_lease.id();
+        compile_instance.work_path = self.tmp_path.clone();
+        compile_instance.script_path = compiler_path;
+        compile_instance.args = args;
+        compile_instance.limit = compile_limit;
+        compile_instance.processes = self.language_config.processes;
+        compile_instance.sandbox_dirs = self.language_config.sandbox_dirs.clone();
+        compile_instance.config = self.config.clone();
+        compile_instance.init().await?;
+        let compile_output = compile_instance.run().await?;
+        drop(compile_instance);
+        drop(box_lease);
         log::debug!("compile output {compile_output:?}");
-        let compile_output_args = String::from_utf8(compile_output.stdout.clone())?
+
+        let compile_output_args = compile_output
+            .stdout
             .lines()
             .map(|s| s.to_string())
             .collect::<Vec<String>>();
 
-        let return_code: i32 = compile_output_args
-            .get(0)
-            .map_or(1, |s| s.parse::<i32>().unwrap_or(1));
-
-        match return_code {
-            0 => {
+        match compile_output.result.status {
+            RunVerdict::VerdictTLE => {
+                self.compile_message = String::from("Compilation timed out");
                 _ = self
                     .message_handler
-                    .send(SubmissionMessage::Status(SubmissionStatus::Compiled))
+                    .send(SubmissionMessage::Status(
+                        SubmissionStatus::CompilationTimeout,
+                    ))
                     .await;
+                Ok(false)
             }
-            _ => {
+            RunVerdict::VerdictMLE => {
+                let compile_message = format!(
+                    "Compiler exceeded the memory limit of {} MB\n{}",
+                    compile_limit.memory_limit,
+                    self.read_compile_message().await
+                );
+                self.compile_message = compile_message.clone();
                 _ = self
                     .message_handler
                     .send(SubmissionMessage::Status(
-                        SubmissionStatus::CompilationError(String::from_utf8(
-                            compile_output.stdout,
-                        )?),
+                        SubmissionStatus::CompilationError(compile_message),
                     ))
                     .await;
+                Ok(false)
+            }
+            status => {
+                let return_code: i32 = match status {
+                    RunVerdict::VerdictOK => compile_output_args
+                        .first()
+                        .map_or(1, |s| s.parse::<i32>().unwrap_or(1)),
+                    _ => 1,
+                };
+                let bin_path = match return_code {
+                    0 => Some(PathBuf::from(
+                        compile_output_args
+                            .get(1)
+                            .ok_or(GraderError::invalid_index())?,
+                    )),
+                    _ => None,
+                };
+                let compiled = CachedCompile {
+                    return_code,
+                    bin_path,
+                    message: self.read_compile_message().await,
+                };
+                // Only the outcomes of a compile script that ran to completion
+                // are deterministic enough to reuse.
+                if let (Some(key), RunVerdict::VerdictOK) = (&cache_key, status) {
+                    if let Err(e) = cache.store(key, &compiled).await {
+                        log::warn!("unable to store compile cache entry {key}: {e}");
+                    }
+                }
+                Ok(self.finish_compile(compiled).await)
             }
         }
+    }
 
-        if return_code == 0 {
-            self.bin_path = PathBuf::from(
-                compile_output_args
-                    .get(1)
-                    .ok_or(GraderError::invalid_index())?,
-            );
-            Ok(true)
+    async fn finish_compile(&mut self, compiled: CachedCompile) -> bool
+    where
+        T: Sink<SubmissionMessage> + std::marker::Unpin,
+    {
+        self.compile_message = compiled.message.clone();
+        let status = match compiled.bin_path {
+            Some(bin_path) if compiled.return_code == 0 => {
+                self.bin_path = bin_path;
+                SubmissionStatus::Compiled
+            }
+            _ => SubmissionStatus::CompilationError(compiled.message),
+        };
+        let compiled = matches!(status, SubmissionStatus::Compiled);
+        _ = self
+            .message_handler
+            .send(SubmissionMessage::Status(status))
+            .await;
+        compiled
+    }
+
+    /// The diagnostics the compile script left in `compileMsg`, with the
+    /// temporary directory stripped from the paths they mention.
+    async fn read_compile_message(&self) -> String {
+        let compile_message =
+            read_excerpt(&self.tmp_path.join("compileMsg"), COMPILE_MESSAGE_SIZE).await;
+        // The compile script sees the canonical path of the directory.
+        let canonical_path = std::fs::canonicalize(&self.tmp_path).ok();
+        [Some(&self.tmp_path), canonical_path.as_ref()]
+            .iter()
+            .flatten()
+            .filter_map(|path| path.to_str())
+            .fold(compile_message, |message, path| {
+                message.replace(&format!("{path}/"), "")
+            })
+    }
+
+    /// The time limit in seconds and memory limit in megabytes for this
+    /// submission's language. An entry for the language in the manifest's
+    /// `limit` table is used as is, otherwise the language's time multiplier
+    /// and memory allowance adjust the task's limits.
+    pub fn limits(&self) -> GraderResult<(f64, u64)> {
+        if let Some(limit) = self
+            .task_manifest
+            .limit
+            .as_ref()
+            .and_then(|limit| limit.get(&self.language))
+        {
+            return Ok(*limit);
+        }
+
+        let time_limit = self
+            .task_manifest
+            .time_limit
+            .ok_or(GraderError::invalid_value())?;
+        let memory_limit = self
+            .task_manifest
+            .memory_limit
+            .ok_or(GraderError::invalid_value())?;
+        Ok((
+            time_limit * self.language_config.time_multiplier,
+            memory_limit + self.language_config.memory_allowance,
+        ))
+    }
+
+    /// Factor applied to the task's time limit for this language, 1 when the
+    /// manifest sets the language's limits itself.
+    fn time_multiplier(&self) -> f64 {
+        let overridden = self
+            .task_manifest
+            .limit
+            .as_ref()
+            .is_some_and(|limit| limit.contains_key(&self.language));
+        if overridden {
+            1.0
         } else {
-            Ok(false)
+            self.language_config.time_multiplier
+        }
+    }
+
+    /// Reserve an isolate box for one compile or run. The lease must outlive
+    /// the instance using it, whose drop cleans the box up.
+    async fn lease_box(&self) -> BoxLease {
+        BoxLease::acquire(self.config.boxes).await
+    }
+
+    fn checker_path(&self) -> GraderResult<PathBuf> {
+        match &self.task_manifest.checker {
+            Some(file) => join_within(&self.config.scripts_path().join("checker_scripts"), file),
+            None => Ok(self.task_path.join("checker")),
+        }
+    }
+
+    /// The runner script of the submission's language.
+    async fn runner_path(&self) -> GraderResult<PathBuf> {
+        Ok(match self.language_config.runner_script() {
+            Some(script) => {
+                let runner_path = self.tmp_path.join("runner_script");
+                write_script(&runner_path, &script).await?;
+                runner_path
+            }
+            None => self
+                .config
+                .scripts_path()
+                .join("runner_scripts")
+                .join(&self.language),
+        })
+    }
+
+    /// Run the compiled program once in the sandbox with the submission's
+    /// limits, reading `input_path` and copying its output to `output_path`
+    /// when it finishes normally.
+    async fn execute(
+        &self,
+        runner: &Path,
+        input_path: &Path,
+        output_path: &Path,
+        stderr_path: &Path,
+    ) -> GraderResult<instance::InstanceResult> {
+        let (time_limit, memory_limit) = self.limits()?;
+        let box_lease = self.lease_box().await;
+        let mut instance = instance! {
+            time_limit: time_limit,
+            memory_limit: memory_limit * 1000,
+            bin_path: self.bin_path.clone(),
+            input_path: input_path.to_path_buf(),
+            output_path: output_path.to_path_buf(),
+            stderr_path: stderr_path.to_path_buf(),
+            runner_path: runner.to_path_buf(),
+            processes: self.language_config.processes,
+            sandbox_dirs: self.language_config.sandbox_dirs.clone(),
+            config: self.config.clone(),
+            box_id: box_lease.id()
+        };
+
+        instance.init().await?;
+        instance.run().await
+    }
+
+    /// Run like `execute`. When the time is within the configured margin of
+    /// the limit, the test runs the configured number of attempts and the one
+    /// with the minimum or median time is kept, along with its output and
+    /// stderr. Returns it with the number of attempts.
+    async fn execute_borderline(
+        &self,
+        runner: &Path,
+        input_path: &Path,
+        output_path: &Path,
+        stderr_path: &Path,
+    ) -> GraderResult<(instance::InstanceResult, u64)> {
+        let rerun = self.config.rerun;
+        let (time_limit, _) = self.limits()?;
+        let first = self
+            .execute(runner, input_path, output_path, stderr_path)
+            .await?;
+        let close = |result: &instance::InstanceResult| {
+            matches!(
+                result.status,
+                RunVerdict::VerdictOK | RunVerdict::VerdictTLE
+            ) && rerun.is_borderline(result.time_usage, time_limit)
+        };
+        if !close(&first) {
+            return Ok((first, 1));
+        }
+
+        let attempt_path = |path: &Path, attempt: u64| {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(format!("_attempt_{attempt}"));
+            path.with_file_name(name)
+        };
+        let mut attempts = vec![(first, output_path.to_path_buf(), stderr_path.to_path_buf())];
+        for attempt in 1..rerun.attempts {
+            let output = attempt_path(output_path, attempt);
+            let stderr = attempt_path(stderr_path, attempt);
+            let result = self.execute(runner, input_path, &output, &stderr).await?;
+            attempts.push((result, output, stderr));
+        }
+        let count = attempts.len() as u64;
+
+        attempts.sort_by(|a, b| a.0.time_usage.total_cmp(&b.0.time_usage));
+        let chosen = match rerun.time {
+            RerunTime::Min => 0,
+            RerunTime::Median => (attempts.len() - 1) / 2,
+        };
+        let (result, output, stderr) = attempts.swap_remove(chosen);
+        for (kept, path) in [(output, output_path), (stderr, stderr_path)].iter() {
+            if kept != path {
+                fs::remove_file(path).await.ok();
+                fs::rename(kept, path).await.ok();
+            }
+        }
+        for (_, output, stderr) in attempts {
+            if output != output_path {
+                fs::remove_file(output).await.ok();
+                fs::remove_file(stderr).await.ok();
+            }
+        }
+        Ok((result, count))
+    }
+
+    /// Run the compiled program on `input_path` and keep its output in
+    /// `output_path`.
+    async fn execute_once(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+    ) -> GraderResult<RunVerdict> {
+        let runner = self.runner_path().await?;
+        let stderr_path = self.tmp_path.join("stderr");
+        Ok(self
+            .execute(&runner, input_path, output_path, &stderr_path)
+            .await?
+            .status)
+    }
+
+    /// Run the compiled program on `input_path` and judge its output against
+    /// `sol_path` with the task's checker.
+    async fn judge_input(
+        &self,
+        input_path: &Path,
+        output_path: &Path,
+        sol_path: &Path,
+    ) -> GraderResult<RunResult> {
+        let runner = self.runner_path().await?;
+        let stderr_path = self.tmp_path.join("stderr");
+        let instance_result = self
+            .execute(&runner, input_path, output_path, &stderr_path)
+            .await?;
+
+        let (time_limit, memory_limit) = self.limits()?;
+        let mut run_result = RunResult::from(
+            self.submission_id.clone(),
+            0,
+            instance_result.time_usage,
+            instance_result.memory_usage,
+        );
+        run_result.attempts = 1;
+        run_result.time_limit = time_limit;
+        run_result.memory_limit = memory_limit;
+        run_result.stderr = read_excerpt(&stderr_path, STDERR_EXCERPT_SIZE).await;
+        match instance_result.status {
+            RunVerdict::VerdictOK => {
+                check(
+                    &self.checker_path()?,
+                    input_path,
+                    output_path,
+                    sol_path,
+                    &mut run_result,
+                )
+                .await?
+            }
+            verdict => run_result.status = String::from(verdict_status(&verdict)),
+        }
+        if run_result.message.is_empty() {
+            run_result.message = self
+                .config
+                .message(&run_result.status, self.locale.as_deref());
+        }
+        Ok(run_result)
+    }
+
+    /// Compile the submission if needed and run it once on `input`, without a
+    /// checker or grouper.
+    pub async fn test_run(&mut self, input: &[u8]) -> GraderResult<TestRunResult>
+    where
+        T: Sink<SubmissionMessage> + std::marker::Unpin,
+    {
+        check_input_size(input)?;
+        let mut result = TestRunResult {
+            submission_id: self.submission_id.clone(),
+            ..Default::default()
+        };
+        if self.bin_path == PathBuf::new() && !self.compile().await? {
+            result.status = String::from("Compilation Error");
+            result.compile_message = self.compile_message.clone();
+            return Ok(result);
         }
+        result.compile_message = self.compile_message.clone();
+
+        let input_path = self.tmp_path.join("test_run_input");
+        let output_path = self.tmp_path.join("test_run_output");
+        let stderr_path = self.tmp_path.join("test_run_stderr");
+        fs::write(&input_path, input).await?;
+        let runner = self.runner_path().await?;
+        let instance_result = self
+            .execute(&runner, &input_path, &output_path, &stderr_path)
+            .await?;
+
+        let (time_limit, memory_limit) = self.limits()?;
+        result.status = String::from(verdict_status(&instance_result.status));
+        result.time_usage = instance_result.time_usage;
+        result.memory_usage = instance_result.memory_usage;
+        result.time_limit = time_limit;
+        result.memory_limit = memory_limit;
+        result.stdout = read_excerpt(&output_path, TEST_RUN_OUTPUT_SIZE).await;
+        result.stderr = read_excerpt(&stderr_path, STDERR_EXCERPT_SIZE).await;
+        Ok(result)
     }
 
     pub async fn run_each(
@@ -233,29 +855,22 @@ impl<T> Submission<T> {
             .message_handler
             .send(SubmissionMessage::Status(SubmissionStatus::Running(index)))
             .await;
-        let input_path = self
-            .task_path
-            .join("testcases")
-            .join(format!("{}.in", index));
+        let testcase = self
+            .test_groups
+            .iter()
+            .flat_map(|group| group.testcases.iter())
+            .nth((index as usize).wrapping_sub(1))
+            .ok_or(GraderError::invalid_index())?;
+        let input_path = testcase.input_path.clone();
         let output_path = self.tmp_path.join(format!("output_{}", index));
-        let sol_path = self
-            .task_path
-            .join("testcases")
-            .join(format!("{}.sol", index));
+        let stderr_path = self.tmp_path.join(format!("stderr_{}", index));
+        let sol_path = testcase.sol_path.clone();
+        let sample = testcase.sample;
 
-        let mut instance = instance! {
-            time_limit: self.task_manifest.time_limit.ok_or(GraderError::invalid_value())?,
-            memory_limit: self.task_manifest.memory_limit.ok_or(GraderError::invalid_value())? * 1000,
-            bin_path: self.bin_path.clone(),
-            input_path: input_path.clone(),
-            output_path: output_path.clone(),
-            runner_path: runner.to_path_buf(),
-            box_id: self.submission_id.clone().parse::<u64>().unwrap() % 1000
-        };
-
-        instance.init().await?;
-
-        let instance_result = instance.run().await?;
+        let (time_limit, memory_limit) = self.limits()?;
+        let (instance_result, attempts) = self
+            .execute_borderline(runner, &input_path, &output_path, &stderr_path)
+            .await?;
 
         let mut run_result = RunResult::from(
             self.submission_id.to_owned(),
@@ -263,47 +878,56 @@ impl<T> Submission<T> {
             instance_result.time_usage,
             instance_result.memory_usage,
         );
+        run_result.attempts = attempts;
+        run_result.sample = sample;
+        run_result.time_limit = time_limit;
+        run_result.memory_limit = memory_limit;
+        run_result.stderr = read_excerpt(&stderr_path, STDERR_EXCERPT_SIZE).await;
 
-        run_result.status = match instance_result.status {
+        match instance_result.status {
             RunVerdict::VerdictOK => {
-                let args = vec![&input_path, &output_path, &sol_path];
-                log::debug!("{input_path:?}, {output_path:?}, {sol_path:?}");
-                let checker_result = Command::new(checker).args(args).output()?;
-                log::debug!("{checker_result:?}\n");
-                let checker_output = String::from_utf8(checker_result.stdout)?
-                    .trim_end_matches('\n')
-                    .lines()
-                    .map(|s| s.to_string())
-                    .collect::<Vec<String>>();
-
-                run_result.score = checker_output
-                    .get(1)
-                    .ok_or(GraderError::invalid_index())?
-                    .parse()?;
-                run_result.message = checker_output
-                    .get(2)
-                    .map_or(String::new(), |v| v.to_owned());
-
-                checker_output
-                    .get(0)
-                    .ok_or(GraderError::invalid_index())?
-                    .as_str()
-                    .to_owned()
+                check(
+                    checker,
+                    &input_path,
+                    &output_path,
+                    &sol_path,
+                    &mut run_result,
+                )
+                .await?;
+                if let Some(scoring) = &self.task_manifest.scoring {
+                    scoring.apply(&mut run_result, self.time_multiplier());
+                }
             }
-            RunVerdict::VerdictTLE => String::from("Time Limit Exceeded"),
-            RunVerdict::VerdictMLE => String::from("Memory Limit Exceeded"),
-            RunVerdict::VerdictRE => String::from("Runtime Error"),
-            RunVerdict::VerdictSG => String::from("Signal Error"),
-            _ => String::from("Judge Error"),
-        };
+            verdict => run_result.status = String::from(verdict_status(&verdict)),
+        }
+
+        if sample {
+            run_result.feedback = Some(TestFeedback {
+                output: read_excerpt(&output_path, FEEDBACK_EXCERPT_SIZE).await,
+                expected_output: read_excerpt(&sol_path, FEEDBACK_EXCERPT_SIZE).await,
+                checker_message: run_result.message.clone(),
+                diff: if self.task_manifest.output_diff && run_result.status == "Incorrect" {
+                    diff::diff_files(&output_path, &sol_path).await
+                } else {
+                    None
+                },
+            });
+        } else {
+            // Hidden tests get the default message of their status. Their
+            // stderr is kept for admins and stripped by whoever shows the
+            // result to contestants.
+            run_result.message.clear();
+        }
 
         if run_result.message.is_empty() {
-            run_result.message = get_message(&run_result.status);
+            run_result.message = self
+                .config
+                .message(&run_result.status, self.locale.as_deref());
         }
 
         _ = self
             .message_handler
-            .send(SubmissionMessage::RunResult(run_result.clone()))
+            .send(SubmissionMessage::RunResult(Box::new(run_result.clone())))
             .await;
         Ok(run_result)
     }
@@ -321,38 +945,20 @@ impl<T> Submission<T> {
             });
         }
 
-        let checker =
-            self.task_manifest
-                .checker
-                .as_ref()
-                .map_or(self.task_path.join("checker"), |file| {
-                    get_base_path()
-                        .join("scripts")
-                        .join("checker_scripts")
-                        .join(file)
-                });
-        let grouper =
-            self.task_manifest
-                .grouper
-                .as_ref()
-                .map_or(self.task_path.join("grouper"), |file| {
-                    get_base_path()
-                        .join("scripts")
-                        .join("grouper_scripts")
-                        .join(file)
-                });
-        let runner = get_base_path()
-            .join("scripts")
-            .join("runner_scripts")
-            .join(&self.language);
+        let checker = self.checker_path()?;
+        let grouper = match &self.task_manifest.grouper {
+            Some(file) => join_within(&self.config.scripts_path().join("grouper_scripts"), file)?,
+            None => self.task_path.join("grouper"),
+        };
+        let runner = self.runner_path().await?;
 
         let mut last_test = 1;
         let mut total_score: f64 = 0.0;
         let mut total_full_score: f64 = 0.0;
         let mut group_results = Vec::new();
-        for (group_index, (full_score, tests)) in
-            self.task_manifest.groups.clone().iter().enumerate()
-        {
+        for (group_index, group) in self.test_groups.clone().iter().enumerate() {
+            let full_score = &group.full_score;
+            let tests = group.testcases.len() as u64;
             total_full_score += full_score;
 
             let mut skip = false;
@@ -375,12 +981,21 @@ impl<T> Submission<T> {
                 group_result.run_result.push(run_result);
             }
             if !skip {
-                let grouper_result = Command::new(&grouper).args(args).output()?;
-                group_result.score = String::from_utf8(grouper_result.stdout)?
-                    .trim_end_matches('\n')
-                    .parse()?;
+                match output_with_timeout(Command::new(&grouper).args(args), GROUPER_TIMEOUT).await
+                {
+                    Ok(grouper_result) => {
+                        group_result.score = String::from_utf8(grouper_result.stdout)?
+                            .trim_end_matches('\n')
+                            .parse()?;
 
-                total_score += group_result.score;
+                        total_score += group_result.score;
+                    }
+                    Err(e @ GraderError::Timeout { .. }) => {
+                        log::warn!("grouper failed on group {}: {e}", group_index + 1);
+                        group_result.status = String::from("Judge Error");
+                    }
+                    Err(e) => return Err(e),
+                }
             }
             _ = self
                 .message_handler
@@ -408,6 +1023,80 @@ impl<T> Submission<T> {
     }
 }
 
+fn check_input_size(input: &[u8]) -> GraderResult<()> {
+    if input.len() > CUSTOM_INPUT_SIZE {
+        return Err(GraderError::input_limit_exceeded(format!(
+            "input is {} bytes, at most {CUSTOM_INPUT_SIZE} are allowed",
+            input.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Judge the output of a run that finished normally, setting the status, score
+/// and message of `run_result` from the checker's output. A checker that times
+/// out gives a `Judge Error`.
+async fn check(
+    checker: &Path,
+    input_path: &Path,
+    output_path: &Path,
+    sol_path: &Path,
+    run_result: &mut RunResult,
+) -> GraderResult<()> {
+    let args = vec![input_path, output_path, sol_path];
+    log::debug!("{input_path:?}, {output_path:?}, {sol_path:?}");
+    let checker_result =
+        match output_with_timeout(Command::new(checker).args(args), CHECKER_TIMEOUT).await {
+            Ok(checker_result) => checker_result,
+            Err(e @ GraderError::Timeout { .. }) => {
+                log::warn!("checker failed on {input_path:?}: {e}");
+                run_result.status = String::from("Judge Error");
+                run_result.score = 0.0;
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+    log::debug!("{checker_result:?}\n");
+    let checker_output = String::from_utf8(checker_result.stdout)?
+        .trim_end_matches('\n')
+        .lines()
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>();
+
+    run_result.score = checker_output
+        .get(1)
+        .ok_or(GraderError::invalid_index())?
+        .parse()?;
+    run_result.message = checker_output
+        .get(2)
+        .map_or(String::new(), |v| v.to_owned());
+    run_result.status = checker_output
+        .first()
+        .ok_or(GraderError::invalid_index())?
+        .as_str()
+        .to_owned();
+    Ok(())
+}
+
+/// The status of a run that the checker is not asked about, `OK` when the
+/// program finished normally.
+fn verdict_status(verdict: &RunVerdict) -> &'static str {
+    match verdict {
+        RunVerdict::VerdictOK => "OK",
+        RunVerdict::VerdictTLE => "Time Limit Exceeded",
+        RunVerdict::VerdictMLE => "Memory Limit Exceeded",
+        RunVerdict::VerdictRE => "Runtime Error",
+        RunVerdict::VerdictSG => "Signal Error",
+        RunVerdict::VerdictXX => "Judge Error",
+    }
+}
+
+async fn write_script(path: &Path, script: &str) -> GraderResult<()> {
+    fs::write(path, script).await?;
+    fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).await?;
+    Ok(())
+}
+
 impl<T> Drop for Submission<T> {
     fn drop(&mut self) {
         std::fs::remove_dir_all(&self.tmp_path).ok();
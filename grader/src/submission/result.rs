@@ -1,3 +1,4 @@
+use crate::submission::diff::OutputDiff;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
@@ -9,6 +10,35 @@ pub struct RunResult {
     pub memory_usage: u64,
     pub score: f64,
     pub message: String,
+    /// Time limit in seconds the test ran with, after language adjustments.
+    #[serde(default)]
+    pub time_limit: f64,
+    /// Memory limit in megabytes the test ran with, after language adjustments.
+    #[serde(default)]
+    pub memory_limit: u64,
+    /// Times the test was run, more than one when its time was close to the
+    /// limit. 0 for skipped tests.
+    #[serde(default)]
+    pub attempts: u64,
+    /// Whether the test is a sample, whose details may be shown to contestants.
+    #[serde(default)]
+    pub sample: bool,
+    /// Beginning of the program's stderr. Only meant for contestants when
+    /// `sample` is set.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub stderr: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<TestFeedback>,
+}
+
+/// Details reported only for sample tests.
+#[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct TestFeedback {
+    pub output: String,
+    pub expected_output: String,
+    pub checker_message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<OutputDiff>,
 }
 
 impl RunResult {
@@ -30,6 +60,10 @@ pub struct GroupResult {
     pub submission_id: String,
     pub group_index: u64,
     pub run_result: Vec<RunResult>,
+    /// `Judge Error` when the grouper failed to score the group, empty
+    /// otherwise.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub status: String,
 }
 
 impl GroupResult {
@@ -43,6 +77,22 @@ impl GroupResult {
     }
 }
 
+/// Outcome of running a submission once on custom input.
+#[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct TestRunResult {
+    pub submission_id: String,
+    /// `OK`, a limit or error verdict, or `Compilation Error`.
+    pub status: String,
+    pub time_usage: f64,
+    pub memory_usage: u64,
+    pub time_limit: f64,
+    pub memory_limit: u64,
+    /// Beginning of the program's output, empty unless it finished normally.
+    pub stdout: String,
+    pub stderr: String,
+    pub compile_message: String,
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct SubmissionResult {
     pub score: f64,
@@ -50,3 +100,45 @@ pub struct SubmissionResult {
     pub submission_id: String,
     pub group_result: Vec<GroupResult>,
 }
+
+#[derive(Default, Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+pub enum HackVerdict {
+    /// The hacked submission was judged correct on the input.
+    #[default]
+    Unsuccessful,
+    Successful,
+    /// The task's validator rejected the input.
+    InvalidInput,
+}
+
+/// Outcome of a hack against a submission.
+#[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct HackResult {
+    pub submission_id: String,
+    pub verdict: HackVerdict,
+    /// Output of the validator when it rejected the input.
+    pub validator_message: String,
+    /// Run of the hacked submission, unset when the input was invalid.
+    pub run_result: Option<RunResult>,
+}
+
+/// Outcome of a stress test.
+#[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct StressResult {
+    /// Number of generated inputs the candidate ran on.
+    pub iterations: u64,
+    /// The first input the candidate failed on, if any.
+    pub failure: Option<StressFailure>,
+}
+
+#[derive(Default, Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct StressFailure {
+    /// Seed the generator was given.
+    pub seed: u64,
+    pub input: String,
+    /// Output of the brute-force solution.
+    pub expected_output: String,
+    /// Output of the candidate solution.
+    pub output: String,
+    pub run_result: RunResult,
+}
@@ -0,0 +1,123 @@
+//! Scoring functions of optimization tasks, applied to a test after the
+//! checker accepted its output.
+
+use crate::errors::{GraderError, GraderResult};
+use crate::submission::result::RunResult;
+use yaml_rust::{yaml::Hash, Yaml};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scoring {
+    /// Full score up to `good` seconds, nothing from `bad` seconds, linear in
+    /// between. Both are scaled like the time limit of the language.
+    Time { good: f64, bad: f64 },
+    /// The checker's score is a raw value, scored relative to the best known
+    /// one: `value / best` when maximizing, `best / value` when minimizing.
+    Relative { best: f64, objective: Objective },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    Min,
+    Max,
+}
+
+impl Scoring {
+    pub fn from_yaml(yaml: &Yaml) -> GraderResult<Option<Self>> {
+        let number = |key: &str| {
+            yaml[key]
+                .as_f64()
+                .or_else(|| yaml[key].as_i64().map(|x| x as f64))
+                .ok_or_else(|| GraderError::invalid_task(format!("scoring needs a {key}")))
+        };
+        if yaml.is_badvalue() {
+            return Ok(None);
+        }
+        let scoring = match yaml["type"].as_str() {
+            Some("time") => Scoring::Time {
+                good: number("good")?,
+                bad: number("bad")?,
+            },
+            Some("relative") => Scoring::Relative {
+                best: number("best")?,
+                objective: match yaml["objective"].as_str() {
+                    None | Some("max") => Objective::Max,
+                    Some("min") => Objective::Min,
+                    Some(objective) => {
+                        return Err(GraderError::invalid_task(format!(
+                            "unknown scoring objective {objective:?}"
+                        )))
+                    }
+                },
+            },
+            Some(scoring) => {
+                return Err(GraderError::invalid_task(format!(
+                    "unknown scoring type {scoring:?}"
+                )))
+            }
+            None => return Err(GraderError::invalid_task("scoring needs a type")),
+        };
+        match scoring {
+            Scoring::Time { good, bad } if good >= bad => Err(GraderError::invalid_task(
+                "time scoring needs good below bad",
+            )),
+            Scoring::Relative { best, .. } if best <= 0.0 => Err(GraderError::invalid_task(
+                "relative scoring needs a positive best",
+            )),
+            _ => Ok(Some(scoring)),
+        }
+    }
+
+    pub fn to_yaml(&self) -> Yaml {
+        fn key(name: &str) -> Yaml {
+            Yaml::String(name.to_owned())
+        }
+
+        let mut yaml = Hash::new();
+        match self {
+            Scoring::Time { good, bad } => {
+                yaml.insert(key("type"), key("time"));
+                yaml.insert(key("good"), Yaml::Real(format!("{good:?}")));
+                yaml.insert(key("bad"), Yaml::Real(format!("{bad:?}")));
+            }
+            Scoring::Relative { best, objective } => {
+                yaml.insert(key("type"), key("relative"));
+                yaml.insert(key("best"), Yaml::Real(format!("{best:?}")));
+                let objective = match objective {
+                    Objective::Min => "min",
+                    Objective::Max => "max",
+                };
+                yaml.insert(key("objective"), key(objective));
+            }
+        }
+        Yaml::Hash(yaml)
+    }
+
+    /// Rescale the score of an accepted run, out of 100. A correct run that
+    /// loses points becomes partially correct. `time_multiplier` is the
+    /// factor applied to the time limit of the run's language.
+    pub fn apply(&self, run_result: &mut RunResult, time_multiplier: f64) {
+        if run_result.status != "Correct" && run_result.status != "Partially Correct" {
+            return;
+        }
+
+        run_result.score = match *self {
+            Scoring::Time { good, bad } => {
+                let (good, bad) = (good * time_multiplier, bad * time_multiplier);
+                let factor = (bad - run_result.time_usage) / (bad - good);
+                run_result.score * factor.clamp(0.0, 1.0)
+            }
+            Scoring::Relative { best, objective } => {
+                let value = run_result.score;
+                let ratio = match objective {
+                    Objective::Max => value / best,
+                    Objective::Min if value > 0.0 => best / value,
+                    Objective::Min => 0.0,
+                };
+                100.0 * ratio.clamp(0.0, 1.0)
+            }
+        };
+        if run_result.status == "Correct" && run_result.score < 100.0 {
+            run_result.status = String::from("Partially Correct");
+        }
+    }
+}
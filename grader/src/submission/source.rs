@@ -0,0 +1,219 @@
+//! Files of a submission and the names they are written under.
+
+use crate::archive::{self, ArchiveKind, ExtractLimit};
+use crate::errors::{GraderError, GraderResult};
+use crate::language::Language;
+use crate::utils::check_identifier;
+use std::{
+    collections::BTreeSet,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+/// Where archives are unpacked before their entries are moved into place.
+const STAGING_DIR: &str = ".archive";
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    /// File name such as `solution.cpp` or `Main.java`. Unnamed files take
+    /// the task's expected name at their position or `code_<index>.<extension>`.
+    pub name: Option<String>,
+    pub content: Vec<u8>,
+    /// Unpack the content into the submission directory instead of writing
+    /// it as a single file. The name is ignored.
+    pub archive: Option<ArchiveKind>,
+}
+
+impl SourceFile {
+    pub fn named(name: impl ToString, content: impl Into<Vec<u8>>) -> Self {
+        SourceFile {
+            name: Some(name.to_string()),
+            content: content.into(),
+            archive: None,
+        }
+    }
+
+    pub fn archive(kind: ArchiveKind, content: impl Into<Vec<u8>>) -> Self {
+        SourceFile {
+            name: None,
+            content: content.into(),
+            archive: Some(kind),
+        }
+    }
+}
+
+impl From<Vec<u8>> for SourceFile {
+    fn from(content: Vec<u8>) -> Self {
+        SourceFile {
+            name: None,
+            content,
+            archive: None,
+        }
+    }
+}
+
+impl From<String> for SourceFile {
+    fn from(content: String) -> Self {
+        SourceFile::from(content.into_bytes())
+    }
+}
+
+impl From<&str> for SourceFile {
+    fn from(content: &str) -> Self {
+        SourceFile::from(content.to_owned())
+    }
+}
+
+/// Decide the name of every file in `files`.
+///
+/// When the task lists `expected` files for the language, the submission must
+/// provide exactly those, and unnamed files take the expected name at their
+/// position. Names must be valid identifiers, unique, and must not replace
+/// one of the task's `reserved` compile files.
+pub fn file_names(
+    files: &[SourceFile],
+    language: &Language,
+    expected: Option<&Vec<String>>,
+    reserved: &[String],
+) -> GraderResult<Vec<String>> {
+    if let Some(expected) = expected {
+        if files.len() != expected.len() {
+            return Err(GraderError::invalid_source_files(format!(
+                "expected {} files, got {}",
+                expected.len(),
+                files.len()
+            )));
+        }
+    }
+
+    let names = files
+        .iter()
+        .enumerate()
+        .map(
+            |(idx, file)| match (&file.name, expected, idx, &language.source_name) {
+                (Some(name), ..) => name.clone(),
+                (None, Some(expected), ..) => expected[idx].clone(),
+                (None, None, 0, Some(source_name)) => source_name.clone(),
+                (None, None, ..) => format!("code_{idx}.{}", language.extension),
+            },
+        )
+        .collect::<Vec<_>>();
+
+    let mut seen = BTreeSet::new();
+    for name in &names {
+        check_identifier(name).map_err(|_| {
+            GraderError::invalid_source_files(format!("{name:?} is not a valid file name"))
+        })?;
+        if !seen.insert(name) {
+            return Err(GraderError::invalid_source_files(format!(
+                "{name} is submitted twice"
+            )));
+        }
+        if reserved.contains(name) {
+            return Err(GraderError::invalid_source_files(format!(
+                "{name} is provided by the task"
+            )));
+        }
+    }
+    if let Some(expected) = expected {
+        if let Some(missing) = expected.iter().find(|name| !seen.contains(name)) {
+            return Err(GraderError::invalid_source_files(format!(
+                "{missing} is missing"
+            )));
+        }
+    }
+    Ok(names)
+}
+
+/// Check that the files of a submission with archives, once unpacked into
+/// `work_path`, are exactly the `expected` ones.
+pub fn check_expected(
+    paths: &[PathBuf],
+    work_path: &Path,
+    expected: &[String],
+) -> GraderResult<()> {
+    let mut seen = BTreeSet::new();
+    for path in paths {
+        let name = path
+            .strip_prefix(work_path)
+            .map_err(|_| GraderError::invalid_path(format!("{path:?} is outside {work_path:?}")))?
+            .to_str()
+            .ok_or(GraderError::invalid_to_str())?;
+        if !expected.iter().any(|expected| expected == name) {
+            return Err(GraderError::invalid_source_files(format!(
+                "{name} is not expected by the task"
+            )));
+        }
+        seen.insert(name);
+    }
+    if let Some(missing) = expected.iter().find(|name| !seen.contains(name.as_str())) {
+        return Err(GraderError::invalid_source_files(format!(
+            "{missing} is missing"
+        )));
+    }
+    Ok(())
+}
+
+/// Unpack an archive into `work_path` and return the files it held, sorted.
+///
+/// The archive is extracted into a staging directory first. Its top-level
+/// entries must then be valid names that are not in `work_path` already, so
+/// an archive cannot replace the task's files or the other submitted files.
+pub fn unpack(
+    content: &[u8],
+    kind: ArchiveKind,
+    work_path: &Path,
+    limit: ExtractLimit,
+) -> GraderResult<Vec<PathBuf>> {
+    let staging_path = work_path.join(STAGING_DIR);
+    fs::remove_dir_all(&staging_path).ok();
+    fs::create_dir(&staging_path)?;
+    let unpacked = unpack_staged(content, kind, work_path, &staging_path, limit);
+    fs::remove_dir_all(&staging_path).ok();
+    unpacked
+}
+
+fn unpack_staged(
+    content: &[u8],
+    kind: ArchiveKind,
+    work_path: &Path,
+    staging_path: &Path,
+    limit: ExtractLimit,
+) -> GraderResult<Vec<PathBuf>> {
+    archive::extract(Cursor::new(content), kind, staging_path, limit)
+        .map_err(GraderError::invalid_source_files)?;
+
+    let mut entries = fs::read_dir(staging_path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    let mut files = Vec::new();
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_str().ok_or(GraderError::invalid_to_str())?;
+        check_identifier(name).map_err(|_| {
+            GraderError::invalid_source_files(format!("{name:?} is not a valid file name"))
+        })?;
+        let path = work_path.join(name);
+        if path.exists() {
+            return Err(GraderError::invalid_source_files(format!(
+                "{name} is already part of the submission"
+            )));
+        }
+        fs::rename(entry.path(), &path)?;
+        collect_files(&path, &mut files)?;
+    }
+    Ok(files)
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> GraderResult<()> {
+    if !path.is_dir() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        collect_files(&entry.path(), files)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Stress testing: compare a candidate solution against a brute-force one on
+//! generated inputs until the task's checker rejects the candidate.
+
+use super::{verdict_status, Submission, SubmissionMessage, TEST_RUN_OUTPUT_SIZE};
+use crate::errors::{GraderError, GraderResult};
+use crate::instance::RunVerdict;
+use crate::submission::result::{StressFailure, StressResult};
+use crate::utils::read_excerpt;
+use futures::sink::Sink;
+use std::path::PathBuf;
+use tokio::fs;
+
+/// Run the three programs on the inputs generated from seeds `1..=iterations`
+/// and stop at the first input the candidate fails on.
+///
+/// The generator reads its seed on stdin and prints an input of the task. The
+/// brute-force solution's output is taken as the expected answer, and the
+/// candidate is judged against it with the task's checker. All three run in the
+/// sandbox with the limits of the candidate's task.
+///
+/// A generator or brute-force solution that does not compile or does not
+/// finish normally gives `GraderError::StressFailed`, and a candidate that
+/// does not compile gives `GraderError::CompilationFailed`.
+///
+/// The generator and the brute-force solution are meant to be built with
+/// `Submission::try_helper_from_config`, so the task's constraints on
+/// contestant code do not apply to them. The `stress` binary of the interface
+/// runs this from the command line.
+pub async fn stress<G, B, C>(
+    generator: &mut Submission<G>,
+    brute: &mut Submission<B>,
+    candidate: &mut Submission<C>,
+    iterations: u64,
+) -> GraderResult<StressResult>
+where
+    G: Sink<SubmissionMessage> + std::marker::Unpin,
+    B: Sink<SubmissionMessage> + std::marker::Unpin,
+    C: Sink<SubmissionMessage> + std::marker::Unpin,
+{
+    if generator.bin_path == PathBuf::new() && !generator.compile().await? {
+        return Err(GraderError::stress_failed(format!(
+            "generator does not compile: {}",
+            generator.compile_message
+        )));
+    }
+    if brute.bin_path == PathBuf::new() && !brute.compile().await? {
+        return Err(GraderError::stress_failed(format!(
+            "brute-force solution does not compile: {}",
+            brute.compile_message
+        )));
+    }
+    if candidate.bin_path == PathBuf::new() && !candidate.compile().await? {
+        return Err(GraderError::compilation_failed(format!(
+            "candidate solution does not compile: {}",
+            candidate.compile_message
+        )));
+    }
+
+    let seed_path = generator.tmp_path.join("stress_seed");
+    let input_path = candidate.tmp_path.join("stress_input");
+    let sol_path = brute.tmp_path.join("stress_answer");
+    let output_path = candidate.tmp_path.join("stress_output");
+    for seed in 1..=iterations {
+        fs::write(&seed_path, format!("{seed}\n")).await?;
+        let verdict = generator.execute_once(&seed_path, &input_path).await?;
+        if verdict != RunVerdict::VerdictOK {
+            return Err(GraderError::stress_failed(format!(
+                "generator finished with {} on seed {seed}",
+                verdict_status(&verdict)
+            )));
+        }
+        let verdict = brute.execute_once(&input_path, &sol_path).await?;
+        if verdict != RunVerdict::VerdictOK {
+            return Err(GraderError::stress_failed(format!(
+                "brute-force solution finished with {} on seed {seed}",
+                verdict_status(&verdict)
+            )));
+        }
+
+        let run_result = candidate
+            .judge_input(&input_path, &output_path, &sol_path)
+            .await?;
+        if run_result.status != "Correct" {
+            return Ok(StressResult {
+                iterations: seed,
+                failure: Some(StressFailure {
+                    seed,
+                    input: read_excerpt(&input_path, TEST_RUN_OUTPUT_SIZE).await,
+                    expected_output: read_excerpt(&sol_path, TEST_RUN_OUTPUT_SIZE).await,
+                    output: read_excerpt(&output_path, TEST_RUN_OUTPUT_SIZE).await,
+                    run_result,
+                }),
+            });
+        }
+    }
+
+    Ok(StressResult {
+        iterations,
+        failure: None,
+    })
+}
@@ -0,0 +1,274 @@
+use crate::archive::{self, ArchiveKind, ExtractLimit};
+use crate::errors::{GraderError, GraderResult};
+use crate::submission::manifest::{Manifest, TestSelector};
+use sha2::{Digest, Sha256};
+use std::{
+    cmp::Ordering,
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+};
+
+const SOLUTION_EXTENSIONS: [&str; 3] = ["sol", "ans", "out"];
+const ARCHIVE_NAMES: [&str; 3] = ["testcases.zip", "testcases.tar.zst", "testcases.tar"];
+
+static EXTRACTION_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Testcase {
+    pub input_path: PathBuf,
+    pub sol_path: PathBuf,
+    pub sample: bool,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TestGroup {
+    pub full_score: f64,
+    pub testcases: Vec<Testcase>,
+}
+
+/// Return the directory holding the testcases of the task at `task_path`.
+///
+/// This is `testcases` inside the task when it exists. Otherwise the task's
+/// `testcases.zip`, `testcases.tar.zst` or `testcases.tar` is extracted once
+/// into `<temporary_path>/testcases_cache/<task_id>/<stamp>`, where the stamp
+/// hashes the archive's name, size and modification time. A changed archive
+/// is extracted next to the old one, which submissions may still be reading.
+pub async fn prepare(
+    task_id: &str,
+    task_path: &Path,
+    temporary_path: &Path,
+) -> GraderResult<PathBuf> {
+    let testcases_path = task_path.join("testcases");
+    if testcases_path.is_dir() {
+        return Ok(testcases_path);
+    }
+
+    let archive_path = match ARCHIVE_NAMES
+        .iter()
+        .map(|name| task_path.join(name))
+        .find(|path| path.is_file())
+    {
+        Some(archive_path) => archive_path,
+        None => return Ok(testcases_path),
+    };
+    let metadata = fs::metadata(&archive_path)?;
+    let stamp = format!(
+        "{:?} {} {:?}",
+        archive_path.file_name(),
+        metadata.len(),
+        metadata.modified()?
+    );
+    let stamp_hash = Sha256::digest(stamp.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    let cache_path = temporary_path
+        .join("testcases_cache")
+        .join(task_id)
+        .join(stamp_hash);
+    if cache_path.is_dir() {
+        return Ok(cache_path);
+    }
+
+    let extracted_path = cache_path.clone();
+    tokio::task::spawn_blocking(move || extract_cached(&archive_path, &extracted_path)).await??;
+    Ok(cache_path)
+}
+
+/// Extract the archive into a staging directory and move it to `cache_path`
+/// in one step, so a submission never sees a half extracted cache.
+fn extract_cached(archive_path: &Path, cache_path: &Path) -> GraderResult<()> {
+    let kind = ArchiveKind::from_path(archive_path).ok_or(GraderError::invalid_value())?;
+    let staging_path = cache_path.with_extension(format!(
+        "{}.{}.tmp",
+        std::process::id(),
+        EXTRACTION_COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+    ));
+    fs::create_dir_all(&staging_path)?;
+    if let Err(e) = archive::extract(
+        fs::File::open(archive_path)?,
+        kind,
+        &staging_path,
+        ExtractLimit::default(),
+    ) {
+        fs::remove_dir_all(&staging_path).ok();
+        return Err(e);
+    }
+
+    log::info!("extracted {archive_path:?} into {cache_path:?}");
+    if fs::rename(&staging_path, cache_path).is_err() {
+        // Another submission of the same task finished extracting first.
+        fs::remove_dir_all(&staging_path).ok();
+        if !cache_path.is_dir() {
+            return Err(GraderError::invalid_io(format!(
+                "unable to extract {archive_path:?} into {cache_path:?}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the groups of `manifest` into concrete testcases inside `testcases_path`.
+pub fn discover(manifest: &Manifest, testcases_path: &Path) -> GraderResult<Vec<TestGroup>> {
+    let mut last_test = 1;
+    let mut available_inputs = None;
+    manifest
+        .groups
+        .iter()
+        .map(|group| {
+            let inputs = match &group.tests {
+                TestSelector::Count(tests) => (last_test..(last_test + tests))
+                    .map(|index| format!("{index}.in"))
+                    .collect(),
+                TestSelector::Files(files) => files.clone(),
+                TestSelector::Pattern(pattern) => {
+                    let available_inputs = match &mut available_inputs {
+                        Some(inputs) => inputs,
+                        None => available_inputs.insert(list_files(testcases_path)?),
+                    };
+                    let mut inputs = available_inputs
+                        .iter()
+                        .filter(|input| wildcard_match(pattern, input))
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    inputs.sort_by(|a, b| natural_cmp(a, b));
+                    inputs
+                }
+            };
+            last_test += inputs.len() as u64;
+
+            let extension = group
+                .solution_extension
+                .as_deref()
+                .or(manifest.solution_extension.as_deref());
+            Ok(TestGroup {
+                full_score: group.full_score,
+                testcases: inputs
+                    .iter()
+                    .map(|input| {
+                        let input_path = testcases_path.join(input);
+                        if !input_path.is_file() {
+                            return Err(GraderError::invalid_task(format!(
+                                "testcase {input} not found"
+                            )));
+                        }
+                        let sol_path = solution_path(&input_path, extension).ok_or_else(|| {
+                            GraderError::invalid_task(format!("solution for {input} not found"))
+                        })?;
+                        Ok(Testcase {
+                            input_path,
+                            sol_path,
+                            sample: group.sample,
+                        })
+                    })
+                    .collect::<GraderResult<Vec<_>>>()?,
+            })
+        })
+        .collect()
+}
+
+/// The solution sits next to the input with the configured extension, or with
+/// the first of `.sol`, `.ans` and `.out` that exists.
+fn solution_path(input_path: &Path, extension: Option<&str>) -> Option<PathBuf> {
+    match extension {
+        Some(extension) => Some(input_path.with_extension(extension)),
+        None => SOLUTION_EXTENSIONS
+            .iter()
+            .map(|extension| input_path.with_extension(extension))
+            .find(|path| path.is_file()),
+    }
+    .filter(|path| path.is_file())
+}
+
+/// List every file below `root` as a `/`-separated path relative to `root`.
+fn list_files(root: &Path) -> GraderResult<Vec<String>> {
+    let mut files = Vec::new();
+    let mut directories = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for entry in fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else {
+                let relative_path = path
+                    .strip_prefix(root)
+                    .map_err(|_| GraderError::invalid_value())?
+                    .components()
+                    .map(|component| component.as_os_str().to_str())
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or(GraderError::invalid_to_str())?
+                    .join("/");
+                files.push(relative_path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Match `text` against a pattern where `*` matches any run of characters
+/// other than `/` and `?` matches a single one.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == text[t] || (c == '?' && text[t] != '/') => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) if text[matched] != '/' => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    t = matched + 1;
+                }
+                _ => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Compare names so that runs of digits are ordered by value, `2.in` before `10.in`.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_len = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+                let y_len = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+                let (x_digits, y_digits) = (&a[..x_len], &b[..y_len]);
+                let x_trimmed = x_digits.trim_start_matches('0');
+                let y_trimmed = y_digits.trim_start_matches('0');
+                let ordering = x_trimmed
+                    .len()
+                    .cmp(&y_trimmed.len())
+                    .then_with(|| x_trimmed.cmp(y_trimmed))
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = &a[x_len..];
+                b = &b[y_len..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
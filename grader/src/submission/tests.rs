@@ -3,10 +3,12 @@ use super::*;
 use crate::errors::GraderResult;
 use crate::utils::tests::get_example_dir;
 use dotenv::dotenv;
+use scoring::{Objective, Scoring};
 use std::convert::Infallible;
 use tokio::fs;
 use tokio::test;
 
+#[derive(Default)]
 struct MessageSink;
 
 const _: () = {
@@ -51,7 +53,7 @@ async fn should_compile_cpp_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000001", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000001", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     Ok(())
@@ -66,7 +68,7 @@ async fn should_compile_python_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000002", "python", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000002", "python", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     Ok(())
@@ -81,7 +83,7 @@ async fn should_compile_rust_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000003", "rust", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000003", "rust", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     Ok(())
@@ -98,7 +100,7 @@ async fn should_remove_tmp_dir_after_out_of_scope() -> GraderResult<()> {
             .unwrap();
 
         let mut submission =
-            Submission::try_from("a_plus_b", "000004", "cpp", &vec![code], MessageSink).await?;
+            Submission::try_from("a_plus_b", "000004", "cpp", &[code], MessageSink).await?;
         assert!(submission.compile().await?);
         tmp_path = submission.tmp_path.clone();
     }
@@ -117,7 +119,7 @@ async fn should_run_cpp_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000005", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000005", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -135,7 +137,7 @@ async fn should_run_cpp_tle_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000006", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000006", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -168,7 +170,7 @@ async fn should_run_cpp_mle_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000007", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000007", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -201,7 +203,7 @@ async fn should_run_cpp_re_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000008", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000008", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -234,7 +236,7 @@ async fn should_run_cpp_sg_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000009", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000009", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -261,7 +263,7 @@ async fn should_run_cpp_with_header_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b_h", "000010", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b_h", "000010", "cpp", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -279,7 +281,7 @@ async fn should_run_python_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000011", "python", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000011", "python", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -297,7 +299,7 @@ async fn should_run_python_tle_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000012", "python", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000012", "python", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -330,7 +332,7 @@ async fn should_run_python_mle_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000013", "python", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000013", "python", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -363,7 +365,7 @@ async fn should_run_python_re_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000014", "python", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000014", "python", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -396,7 +398,7 @@ async fn should_run_rust_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000015", "rust", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000015", "rust", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -414,7 +416,7 @@ async fn should_run_rust_tle_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000016", "rust", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000016", "rust", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -447,7 +449,7 @@ async fn should_run_rust_mle_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000017", "rust", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000017", "rust", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -480,7 +482,7 @@ async fn should_run_rust_re_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000018", "rust", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000018", "rust", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -513,7 +515,7 @@ async fn should_run_rust_sg_skipped() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000019", "rust", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000019", "rust", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -540,7 +542,7 @@ async fn should_compile_go_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000020", "go", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000020", "go", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     Ok(())
@@ -555,7 +557,7 @@ async fn should_run_go_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000021", "go", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000021", "go", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -573,7 +575,7 @@ async fn should_compile_java_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000022", "java", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000022", "java", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     Ok(())
@@ -588,7 +590,7 @@ async fn should_run_java_successfully() -> GraderResult<()> {
         .unwrap();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000023", "java", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000023", "java", &[code], MessageSink).await?;
     assert!(submission.compile().await?);
 
     let _result = submission.run().await?;
@@ -609,8 +611,7 @@ async fn should_handle_messaging() -> GraderResult<()> {
         .await
         .unwrap();
     {
-        let mut submission =
-            Submission::try_from("a_plus_b", "000024", "cpp", &vec![code], tx).await?;
+        let mut submission = Submission::try_from("a_plus_b", "000024", "cpp", &[code], tx).await?;
         assert!(submission.compile().await?);
 
         let _result = submission.run().await?;
@@ -638,10 +639,10 @@ async fn should_compile_error_cpp() -> GraderResult<()> {
     let code = "hello(".to_string();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000025", "cpp", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000025", "cpp", &[code], MessageSink).await?;
     let result = submission.compile().await?;
 
-    assert!(result == false);
+    assert!(!result);
 
     Ok(())
 }
@@ -653,10 +654,10 @@ async fn should_compile_error_python() -> GraderResult<()> {
     let code = "hello(".to_string();
 
     let mut submission =
-        Submission::try_from("a_plus_b", "000026", "python", &vec![code], MessageSink).await?;
+        Submission::try_from("a_plus_b", "000026", "python", &[code], MessageSink).await?;
     let result = submission.compile().await?;
 
-    assert!(result == false);
+    assert!(!result);
 
     Ok(())
 }
@@ -669,7 +670,7 @@ async fn should_error_when_task_not_found() -> GraderResult<()> {
         .await
         .unwrap();
 
-    let submission = Submission::try_from("hello", "000027", "cpp", &vec![code], MessageSink).await;
+    let submission = Submission::try_from("hello", "000027", "cpp", &[code], MessageSink).await;
 
     let error_msg = submission.unwrap_err();
 
@@ -677,3 +678,1073 @@ async fn should_error_when_task_not_found() -> GraderResult<()> {
 
     Ok(())
 }
+
+#[test]
+async fn should_match_testcase_patterns() {
+    use testcase::wildcard_match;
+
+    assert!(wildcard_match("*.in", "01-03.in"));
+    assert!(wildcard_match("sub2/*.in", "sub2/1.in"));
+    assert!(wildcard_match("sub?/1.in", "sub2/1.in"));
+    assert!(!wildcard_match("*.in", "sub2/1.in"));
+    assert!(!wildcard_match("sub2/*.in", "sub2/1.sol"));
+    assert!(!wildcard_match("sub?/1.in", "sub/1.in"));
+}
+
+#[test]
+async fn should_sort_testcases_naturally() {
+    let mut inputs = vec!["10.in", "2.in", "1-10.in", "1-2.in", "a.in"];
+    inputs.sort_by(|a, b| testcase::natural_cmp(a, b));
+
+    assert_eq!(inputs, vec!["1-2.in", "1-10.in", "2.in", "10.in", "a.in"]);
+}
+
+#[test]
+async fn should_discover_testcases_from_manifest() -> GraderResult<()> {
+    use crate::utils::tests::TempDir;
+    use manifest::{Group, TestSelector};
+
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("discover_testcases");
+    fs::create_dir(tmp_dir.0.join("sub2")).await?;
+    for file in [
+        "1.in",
+        "1.sol",
+        "02.in",
+        "02.ans",
+        "sub2/9.in",
+        "sub2/9.out",
+        "sub2/10.in",
+        "sub2/10.out",
+    ] {
+        fs::write(tmp_dir.0.join(file), "").await?;
+    }
+
+    let manifest = Manifest {
+        groups: vec![
+            Group::count(20.0, 1),
+            Group {
+                full_score: 30.0,
+                tests: TestSelector::Files(vec![String::from("02.in")]),
+                solution_extension: None,
+                sample: true,
+            },
+            Group {
+                full_score: 50.0,
+                tests: TestSelector::Pattern(String::from("sub2/*.in")),
+                solution_extension: Some(String::from("out")),
+                sample: false,
+            },
+        ],
+        ..Default::default()
+    };
+
+    let groups = testcase::discover(&manifest, &tmp_dir.0)?;
+
+    let paths = groups
+        .iter()
+        .map(|group| {
+            group
+                .testcases
+                .iter()
+                .map(|testcase| {
+                    (
+                        testcase.input_path.strip_prefix(&tmp_dir.0).unwrap(),
+                        testcase.sol_path.strip_prefix(&tmp_dir.0).unwrap(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        vec![
+            vec![(Path::new("1.in"), Path::new("1.sol"))],
+            vec![(Path::new("02.in"), Path::new("02.ans"))],
+            vec![
+                (Path::new("sub2/9.in"), Path::new("sub2/9.out")),
+                (Path::new("sub2/10.in"), Path::new("sub2/10.out"))
+            ],
+        ]
+    );
+    assert!(groups[1].testcases[0].sample);
+    assert!(!groups[2].testcases[0].sample);
+
+    Ok(())
+}
+
+#[test]
+async fn should_extract_testcase_archive_once() -> GraderResult<()> {
+    use crate::utils::tests::{get_tmp_path, TempDir};
+    use std::io::Write;
+
+    dotenv().ok();
+
+    let tmp_dir = TempDir::new("testcase_archive_task");
+    let write_archive = |answer: &str| -> GraderResult<()> {
+        let mut writer =
+            zip::ZipWriter::new(std::fs::File::create(tmp_dir.0.join("testcases.zip"))?);
+        for (name, content) in [("1.in", "1 2\n"), ("1.sol", answer)] {
+            writer.start_file(name, zip::write::FileOptions::default())?;
+            writer.write_all(content.as_bytes())?;
+        }
+        writer.finish()?;
+        Ok(())
+    };
+    write_archive("3\n")?;
+
+    let task_cache_path = get_tmp_path()
+        .join("testcases_cache")
+        .join("testcase_archive_task");
+    let cache_path =
+        testcase::prepare("testcase_archive_task", &tmp_dir.0, &get_tmp_path()).await?;
+    assert_eq!(cache_path.parent(), Some(task_cache_path.as_path()));
+    assert_eq!(fs::read_to_string(cache_path.join("1.sol")).await?, "3\n");
+
+    fs::write(cache_path.join("1.sol"), "cached\n").await?;
+    testcase::prepare("testcase_archive_task", &tmp_dir.0, &get_tmp_path()).await?;
+    assert_eq!(
+        fs::read_to_string(cache_path.join("1.sol")).await?,
+        "cached\n"
+    );
+
+    // A replaced archive is extracted anew without touching the old cache.
+    write_archive("three\n")?;
+    let new_cache_path =
+        testcase::prepare("testcase_archive_task", &tmp_dir.0, &get_tmp_path()).await?;
+    assert_ne!(new_cache_path, cache_path);
+    assert_eq!(
+        fs::read_to_string(new_cache_path.join("1.sol")).await?,
+        "three\n"
+    );
+    assert_eq!(
+        fs::read_to_string(cache_path.join("1.sol")).await?,
+        "cached\n"
+    );
+
+    fs::remove_dir_all(task_cache_path).await?;
+    Ok(())
+}
+
+#[test]
+async fn should_diff_first_mismatch() {
+    use diff::{diff, OutputDiff};
+
+    assert_eq!(diff("1\n2  3\n", "1\n2 3\n\n"), None);
+    assert_eq!(
+        diff("1\n2\n3 4 6\n5\n6\n7\n", "1\n2\n3 4 5\n5\n6\n7\n"),
+        Some(OutputDiff {
+            line: 3,
+            token: 3,
+            context_line: 1,
+            expected: vec![
+                String::from("1"),
+                String::from("2"),
+                String::from("3 4 5"),
+                String::from("5"),
+                String::from("6")
+            ],
+            output: vec![
+                String::from("1"),
+                String::from("2"),
+                String::from("3 4 6"),
+                String::from("5"),
+                String::from("6")
+            ],
+        })
+    );
+    assert_eq!(
+        diff("1\n", "1\n2\n"),
+        Some(OutputDiff {
+            line: 2,
+            token: 1,
+            context_line: 1,
+            expected: vec![String::from("1"), String::from("2")],
+            output: vec![String::from("1")],
+        })
+    );
+}
+
+#[test]
+async fn should_report_compile_message() -> GraderResult<()> {
+    use futures::StreamExt;
+
+    dotenv().ok();
+
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+
+    let code = "int main() { hello(; }".to_string();
+    let tmp_path;
+    {
+        let mut submission = Submission::try_from("a_plus_b", "000033", "cpp", &[code], tx).await?;
+        assert!(!submission.compile().await?);
+        tmp_path = submission.tmp_path.clone();
+    }
+
+    let msg: Vec<_> = rx.collect().await;
+    let compile_message = match &msg[1] {
+        SubmissionMessage::Status(SubmissionStatus::CompilationError(message)) => message,
+        _ => panic!("expected a compilation error, got {:?}", msg[1]),
+    };
+
+    assert!(compile_message.contains("error"));
+    assert!(!compile_message.contains(tmp_path.to_str().unwrap()));
+
+    Ok(())
+}
+
+#[test]
+async fn should_restore_compile_from_cache() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = crate::utils::tests::TempDir::new("compile_cache_restore");
+    let work_path = tmp_dir.0.join("work");
+    fs::create_dir(&work_path).await?;
+    fs::write(work_path.join("code_0.cpp"), "int main() {}").await?;
+    fs::write(work_path.join("bin"), "binary").await?;
+
+    let script_path = get_example_dir()
+        .join("scripts")
+        .join("compile_scripts")
+        .join("cpp");
+    let code_path = [work_path.join("code_0.cpp")];
+    let header_path = [work_path.join("a_plus_b.h")];
+    fs::write(&header_path[0], "int sum(int a, int b);").await?;
+    let key = compile_cache::cache_key("cpp", &script_path, &code_path, &header_path).await?;
+    let other_key = compile_cache::cache_key("c", &script_path, &code_path, &header_path).await?;
+    assert_ne!(key, other_key);
+
+    fs::write(&header_path[0], "long long sum(int a, int b);").await?;
+    let other_key = compile_cache::cache_key("cpp", &script_path, &code_path, &header_path).await?;
+    assert_ne!(key, other_key);
+
+    let cache = CompileCache::new(tmp_dir.0.join("cache"), 1 << 20);
+    assert_eq!(cache.lookup(&key, &work_path).await?, None);
+    cache
+        .store(
+            &key,
+            &CachedCompile {
+                return_code: 0,
+                bin_path: Some(work_path.join("bin")),
+                message: String::from("warning"),
+            },
+        )
+        .await?;
+
+    let restore_path = tmp_dir.0.join("restore");
+    fs::create_dir(&restore_path).await?;
+    assert_eq!(
+        cache.lookup(&key, &restore_path).await?,
+        Some(CachedCompile {
+            return_code: 0,
+            bin_path: Some(restore_path.join("bin")),
+            message: String::from("warning"),
+        })
+    );
+    assert_eq!(
+        fs::read_to_string(restore_path.join("bin")).await?,
+        "binary"
+    );
+
+    Ok(())
+}
+
+#[test]
+async fn should_evict_least_recently_used_compile() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = crate::utils::tests::TempDir::new("compile_cache_eviction");
+    let cache = CompileCache::new(tmp_dir.0.join("cache"), 64);
+    let compiled = CachedCompile {
+        return_code: 1,
+        bin_path: None,
+        message: "e".repeat(40),
+    };
+
+    cache.store("first", &compiled).await?;
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    cache.store("second", &compiled).await?;
+
+    assert!(!tmp_dir.0.join("cache").join("first").exists());
+    assert!(cache.lookup("second", &tmp_dir.0).await?.is_some());
+
+    Ok(())
+}
+
+#[test]
+async fn should_run_declared_language_successfully() -> GraderResult<()> {
+    dotenv().ok();
+
+    let code = fs::read_to_string(get_example_dir().join("etc").join("a_plus_b.cpp"))
+        .await
+        .unwrap();
+
+    let mut submission =
+        Submission::try_from("a_plus_b", "000037", "cpp17", &[code], MessageSink).await?;
+    assert!(submission.compile().await?);
+
+    let _result = submission.run().await?;
+    assert_eq!(_result.score, 100.0);
+
+    Ok(())
+}
+
+#[test]
+async fn should_rerun_borderline_test() -> GraderResult<()> {
+    use crate::config::{RerunPolicy, RerunTime};
+
+    dotenv().ok();
+
+    let mut config = (*config::get()?).clone();
+    config.rerun = RerunPolicy {
+        margin: 0.9,
+        attempts: 3,
+        time: RerunTime::Median,
+    };
+    // Spends about half of the 1 second limit, within the margin of it.
+    let code = r#"#include <cstdio>
+#include <ctime>
+int main() {
+    long long a, b;
+    scanf("%lld %lld", &a, &b);
+    clock_t start = clock();
+    volatile long long spin = 0;
+    while (clock() - start < CLOCKS_PER_SEC / 2) spin++;
+    printf("%lld\n", a + b);
+}
+"#;
+    let mut submission = Submission::try_from_config(
+        Arc::new(config),
+        "a_plus_b",
+        "000049",
+        "cpp",
+        &[code],
+        MessageSink,
+    )
+    .await?;
+    assert!(submission.compile().await?);
+
+    let checker = submission.checker_path()?;
+    let runner = submission.runner_path().await?;
+    let result = submission.run_each(&checker, &runner, 1).await?;
+    assert_eq!(result.attempts, 3);
+    assert_eq!(result.status, "Correct");
+
+    let mut leftovers = fs::read_dir(&submission.tmp_path).await?;
+    while let Some(entry) = leftovers.next_entry().await? {
+        let name = entry.file_name();
+        assert!(!name.to_string_lossy().contains("_attempt_"), "{:?}", name);
+    }
+    assert!(submission.tmp_path.join("output_1").is_file());
+
+    Ok(())
+}
+
+#[test]
+async fn should_adjust_limits_for_language() -> GraderResult<()> {
+    let mut submission = Submission::<MessageSink>::default();
+    submission.language = String::from("java");
+    submission.language_config = Language {
+        time_multiplier: 2.0,
+        memory_allowance: 256,
+        ..Default::default()
+    };
+    submission.task_manifest = Manifest {
+        time_limit: Some(1.5),
+        memory_limit: Some(64),
+        ..Default::default()
+    };
+    assert_eq!(submission.limits()?, (3.0, 320));
+
+    submission.task_manifest.limit = Some([(String::from("java"), (4.0, 512))].into());
+    assert_eq!(submission.limits()?, (4.0, 512));
+
+    submission.language = String::from("cpp");
+    assert_eq!(submission.limits()?, (3.0, 320));
+
+    Ok(())
+}
+
+#[test]
+async fn should_reject_unknown_language() -> GraderResult<()> {
+    use futures::StreamExt;
+
+    dotenv().ok();
+
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+    let result =
+        Submission::try_from("a_plus_b", "000039", "brainfuck", &[String::new()], tx).await;
+
+    assert!(matches!(
+        result,
+        Err(GraderError::LanguageNotSupported { .. })
+    ));
+    let msg: Vec<_> = rx.collect().await;
+    assert!(matches!(
+        msg[0],
+        SubmissionMessage::Status(SubmissionStatus::LanguageNotSupported)
+    ));
+
+    Ok(())
+}
+
+#[test]
+async fn should_reject_oversized_source() -> GraderResult<()> {
+    use futures::StreamExt;
+
+    dotenv().ok();
+
+    let too_many_files = vec![String::new(); 17];
+    let too_large = vec!["x".repeat(256 * 1024 + 1)];
+    for code in [too_many_files, too_large].iter() {
+        let (tx, rx) = futures::channel::mpsc::unbounded();
+        let result = Submission::try_from("a_plus_b", "000043", "cpp", code, tx).await;
+
+        assert!(matches!(
+            result,
+            Err(GraderError::SourceLimitExceeded { .. })
+        ));
+        let msg: Vec<_> = rx.collect().await;
+        assert!(matches!(
+            msg[0],
+            SubmissionMessage::Status(SubmissionStatus::SourceLimitExceeded)
+        ));
+        assert!(!config::get()?.submissions_path().join("000043").exists());
+    }
+
+    Ok(())
+}
+
+#[test]
+async fn should_name_source_files() -> GraderResult<()> {
+    use source::{file_names, SourceFile};
+
+    let cpp = Language {
+        id: String::from("cpp"),
+        extension: String::from("cpp"),
+        ..Default::default()
+    };
+    let files = vec![
+        SourceFile::from("int main() {}"),
+        SourceFile::named("lib.h", ""),
+    ];
+    assert_eq!(
+        file_names(&files, &cpp, None, &[])?,
+        vec![String::from("code_0.cpp"), String::from("lib.h")]
+    );
+
+    let expected = vec![String::from("solution.cpp"), String::from("lib.h")];
+    assert_eq!(
+        file_names(&files, &cpp, Some(&expected), &[])?,
+        vec![String::from("solution.cpp"), String::from("lib.h")]
+    );
+
+    let invalid = [
+        vec![SourceFile::named("../lib.h", "")],
+        vec![
+            SourceFile::named("lib.h", ""),
+            SourceFile::named("lib.h", ""),
+        ],
+        vec![SourceFile::named("grader.cpp", "")],
+    ];
+    for files in invalid.iter() {
+        assert!(matches!(
+            file_names(files, &cpp, None, &[String::from("grader.cpp")]),
+            Err(GraderError::InvalidSourceFiles { .. })
+        ));
+    }
+    assert!(file_names(
+        &[SourceFile::named("main.cpp", "")],
+        &cpp,
+        Some(&expected),
+        &[]
+    )
+    .is_err());
+    Ok(())
+}
+
+#[test]
+async fn should_write_named_source_files() -> GraderResult<()> {
+    use futures::StreamExt;
+
+    dotenv().ok();
+
+    let code = fs::read_to_string(get_example_dir().join("etc").join("a_plus_b_h.cpp")).await?;
+    let submission = Submission::try_from(
+        "a_plus_b_h",
+        "000044",
+        "cpp",
+        &[source::SourceFile::named("a_plus_b.cpp", code)],
+        MessageSink,
+    )
+    .await?;
+    assert_eq!(
+        submission.code_path,
+        vec![submission.tmp_path.join("a_plus_b.cpp")]
+    );
+    assert!(submission.tmp_path.join("a_plus_b.h").is_file());
+    drop(submission);
+
+    let (tx, rx) = futures::channel::mpsc::unbounded();
+    let result = Submission::try_from(
+        "a_plus_b_h",
+        "000044",
+        "cpp",
+        &[source::SourceFile::named("solution.cpp", "")],
+        tx,
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(GraderError::InvalidSourceFiles { .. })
+    ));
+    let msg: Vec<_> = rx.collect().await;
+    assert!(matches!(
+        msg[0],
+        SubmissionMessage::Status(SubmissionStatus::InvalidSourceFiles)
+    ));
+    Ok(())
+}
+
+fn zip_archive(files: &[(&str, &[u8])]) -> GraderResult<Vec<u8>> {
+    use std::io::Write;
+
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, content) in files {
+        writer.start_file(*name, zip::write::FileOptions::default())?;
+        writer.write_all(content)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+#[test]
+async fn should_unpack_archive_submission() -> GraderResult<()> {
+    use crate::archive::ArchiveKind;
+    use source::SourceFile;
+
+    dotenv().ok();
+
+    let archive = zip_archive(&[("1.out", b"3\n"), ("sub/2.out", b"7\n")])?;
+    let binary = vec![0u8, 159, 146, 150];
+    let submission = Submission::try_from(
+        "a_plus_b",
+        "000045",
+        "cpp",
+        &[
+            SourceFile::named("data.bin", binary.clone()),
+            SourceFile::archive(ArchiveKind::Zip, archive),
+        ],
+        MessageSink,
+    )
+    .await?;
+
+    let tmp_path = submission.tmp_path.clone();
+    assert_eq!(
+        submission.code_path,
+        vec![
+            tmp_path.join("data.bin"),
+            tmp_path.join("1.out"),
+            tmp_path.join("sub").join("2.out"),
+        ]
+    );
+    assert_eq!(fs::read(tmp_path.join("data.bin")).await?, binary);
+    assert_eq!(fs::read_to_string(tmp_path.join("sub/2.out")).await?, "7\n");
+    assert!(!tmp_path.join(".archive").exists());
+    Ok(())
+}
+
+#[test]
+async fn should_reject_unsafe_archive_submission() -> GraderResult<()> {
+    use crate::archive::ArchiveKind;
+    use source::SourceFile;
+
+    dotenv().ok();
+
+    let code = fs::read_to_string(get_example_dir().join("etc").join("a_plus_b_h.cpp")).await?;
+    let replaces_header = zip_archive(&[("a_plus_b.h", b"")])?;
+    let too_large = zip_archive(&[("big.out", &vec![b'0'; 256 * 1024])])?;
+    for archive in [replaces_header, too_large] {
+        let result = Submission::try_from(
+            "a_plus_b_h",
+            "000045_unsafe",
+            "cpp",
+            &[
+                SourceFile::named("a_plus_b.cpp", code.clone()),
+                SourceFile::archive(ArchiveKind::Zip, archive),
+            ],
+            MessageSink,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(GraderError::InvalidSourceFiles { .. })
+        ));
+        assert!(!config::get()?
+            .submissions_path()
+            .join("000045_unsafe")
+            .exists());
+    }
+    Ok(())
+}
+
+#[test]
+async fn should_check_archive_against_source_files() -> GraderResult<()> {
+    use crate::archive::ArchiveKind;
+    use source::SourceFile;
+
+    dotenv().ok();
+
+    let code = fs::read(get_example_dir().join("etc").join("a_plus_b_h.cpp")).await?;
+    let expected = zip_archive(&[("a_plus_b.cpp", &code)])?;
+    let submission = Submission::try_from(
+        "a_plus_b_h",
+        "000045_expected",
+        "cpp",
+        &[SourceFile::archive(ArchiveKind::Zip, expected)],
+        MessageSink,
+    )
+    .await?;
+    assert_eq!(
+        submission.code_path,
+        vec![submission.tmp_path.join("a_plus_b.cpp")]
+    );
+    drop(submission);
+
+    let extra = zip_archive(&[("a_plus_b.cpp", &code), ("extra.cpp", b"")])?;
+    let nested = zip_archive(&[("src/a_plus_b.cpp", &code)])?;
+    let empty = zip_archive(&[])?;
+    for archive in [extra, nested, empty] {
+        let result = Submission::try_from(
+            "a_plus_b_h",
+            "000045_expected",
+            "cpp",
+            &[SourceFile::archive(ArchiveKind::Zip, archive)],
+            MessageSink,
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(GraderError::InvalidSourceFiles { .. })
+        ));
+        assert!(!config::get()?
+            .submissions_path()
+            .join("000045_expected")
+            .exists());
+    }
+    Ok(())
+}
+
+#[test]
+async fn should_reject_path_traversal() -> GraderResult<()> {
+    dotenv().ok();
+
+    for (task_id, submission_id) in [("../a_plus_b", "000042"), ("a_plus_b", "../000042")].iter() {
+        let result =
+            Submission::try_from(task_id, submission_id, "cpp", &[String::new()], MessageSink)
+                .await;
+        assert!(matches!(result, Err(GraderError::InvalidPath { .. })));
+    }
+
+    Ok(())
+}
+
+#[test]
+async fn should_keep_caches_apart_from_submissions() -> GraderResult<()> {
+    dotenv().ok();
+
+    let config = config::get()?;
+    let cache_path = config.temporary_path.join("compile_cache");
+    std::fs::create_dir_all(&cache_path)?;
+    let marker = cache_path.join("should_keep_caches_apart_from_submissions");
+    std::fs::write(&marker, "")?;
+
+    {
+        let submission = Submission::try_from(
+            "a_plus_b",
+            "compile_cache",
+            "cpp",
+            &[String::new()],
+            MessageSink,
+        )
+        .await?;
+        assert_eq!(
+            submission.tmp_path,
+            config.submissions_path().join("compile_cache")
+        );
+    }
+    assert!(marker.is_file());
+    std::fs::remove_file(marker)?;
+
+    Ok(())
+}
+
+#[test]
+async fn should_read_allowed_languages() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = crate::utils::tests::TempDir::new("allowed_languages");
+    let manifest_path = tmp_dir.0.join("manifest.yaml");
+    fs::write(
+        &manifest_path,
+        r#"task_id: "a_plus_b"
+languages: ["cpp", "python"]
+groups:
+  - full_score: 100
+    tests: 1
+"#,
+    )
+    .await?;
+
+    let manifest = Manifest::from(manifest_path.clone())?;
+    assert_eq!(
+        manifest.languages,
+        Some(vec![String::from("cpp"), String::from("python")])
+    );
+
+    manifest.save(&manifest_path)?;
+    assert_eq!(Manifest::from(manifest_path)?, manifest);
+
+    Ok(())
+}
+
+#[test]
+async fn should_reject_negative_source_limits() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = crate::utils::tests::TempDir::new("negative_source_limits");
+    let manifest_path = tmp_dir.0.join("manifest.yaml");
+    fs::write(
+        &manifest_path,
+        r#"task_id: "a_plus_b"
+max_source_files: -1
+groups:
+  - full_score: 100
+    tests: 1
+"#,
+    )
+    .await?;
+
+    assert!(matches!(
+        Manifest::from(manifest_path),
+        Err(GraderError::InvalidTask { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+async fn should_reject_negative_test_count() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = crate::utils::tests::TempDir::new("negative_test_count");
+    let manifest_path = tmp_dir.0.join("manifest.yaml");
+    fs::write(
+        &manifest_path,
+        r#"task_id: "a_plus_b"
+groups:
+  - full_score: 100
+    tests: -1
+"#,
+    )
+    .await?;
+
+    assert!(matches!(
+        Manifest::from(manifest_path),
+        Err(GraderError::InvalidTask { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+async fn should_test_run_with_custom_input() -> GraderResult<()> {
+    dotenv().ok();
+
+    let code = fs::read_to_string(get_example_dir().join("etc").join("a_plus_b.cpp"))
+        .await
+        .unwrap();
+
+    let mut submission =
+        Submission::try_from("a_plus_b", "000046", "cpp", &[code], MessageSink).await?;
+    let result = submission.test_run(b"5 7\n").await?;
+    assert_eq!(result.status, "OK");
+    assert_eq!(result.stdout.trim(), "12");
+
+    Ok(())
+}
+
+#[test]
+async fn should_test_run_compile_error() -> GraderResult<()> {
+    dotenv().ok();
+
+    let mut submission = Submission::try_from(
+        "a_plus_b",
+        "test_run_000046",
+        "cpp",
+        &["int main() { return }"],
+        MessageSink,
+    )
+    .await?;
+    let result = submission.test_run(b"5 7\n").await?;
+    assert_eq!(result.status, "Compilation Error");
+    assert!(result.stdout.is_empty());
+
+    Ok(())
+}
+
+#[test]
+async fn should_reject_invalid_hack_input() -> GraderResult<()> {
+    dotenv().ok();
+
+    let code = fs::read_to_string(get_example_dir().join("etc").join("a_plus_b.cpp"))
+        .await
+        .unwrap();
+
+    let mut submission =
+        Submission::try_from("a_plus_b", "000047", "cpp", &[code], MessageSink).await?;
+    let result = submission.hack(b"5\n").await?;
+    assert_eq!(result.verdict, HackVerdict::InvalidInput);
+    assert_eq!(
+        result.validator_message,
+        "expected two integers on a single line"
+    );
+    assert_eq!(result.run_result, None);
+
+    Ok(())
+}
+
+#[test]
+async fn should_hack_overflowing_submission() -> GraderResult<()> {
+    dotenv().ok();
+
+    let code = fs::read_to_string(get_example_dir().join("etc").join("a_plus_b.cpp"))
+        .await
+        .unwrap();
+
+    let mut submission = Submission::try_from(
+        "a_plus_b",
+        "000047_overflow",
+        "cpp",
+        std::slice::from_ref(&code),
+        MessageSink,
+    )
+    .await?;
+    let result = submission.hack(b"1000000000 1000000000\n").await?;
+    assert_eq!(result.verdict, HackVerdict::Successful);
+    assert_eq!(result.run_result.unwrap().status, "Incorrect");
+    drop(submission);
+
+    let mut submission =
+        Submission::try_from("a_plus_b", "000047_overflow", "cpp", &[code], MessageSink).await?;
+    let result = submission.hack(b"5 7\n").await?;
+    assert_eq!(result.verdict, HackVerdict::Unsuccessful);
+
+    Ok(())
+}
+
+#[test]
+async fn should_fail_hack_against_compile_error() -> GraderResult<()> {
+    dotenv().ok();
+
+    let mut submission = Submission::try_from(
+        "a_plus_b",
+        "000047_compile_error",
+        "cpp",
+        &["int main() { return }"],
+        MessageSink,
+    )
+    .await?;
+    let result = submission.hack(b"5 7\n").await;
+    assert!(
+        matches!(result, Err(GraderError::CompilationFailed { .. })),
+        "{:?}",
+        result
+    );
+
+    Ok(())
+}
+
+#[test]
+async fn should_stress_until_candidate_fails() -> GraderResult<()> {
+    dotenv().ok();
+
+    let read = |file: &str| std::fs::read_to_string(get_example_dir().join("etc").join(file));
+    let mut generator = Submission::try_helper_from_config(
+        config::get()?,
+        "a_plus_b",
+        "000048_generator",
+        "python",
+        &[read("a_plus_b_gen.py")?],
+        MessageSink,
+    )
+    .await?;
+    let brute = std::fs::read_to_string(
+        get_example_dir()
+            .join("tasks")
+            .join("a_plus_b")
+            .join("solutions")
+            .join("a_plus_b.cpp"),
+    )?;
+    let mut brute = Submission::try_helper_from_config(
+        config::get()?,
+        "a_plus_b",
+        "000048_brute",
+        "cpp",
+        &[brute],
+        MessageSink,
+    )
+    .await?;
+    let mut candidate = Submission::try_from(
+        "a_plus_b",
+        "000048",
+        "cpp",
+        &[read("a_plus_b.cpp")?],
+        MessageSink,
+    )
+    .await?;
+
+    let result = stress::stress(&mut generator, &mut brute, &mut candidate, 100).await?;
+    let failure = result.failure.unwrap();
+    assert_eq!(result.iterations, failure.seed);
+    assert_eq!(failure.run_result.status, "Incorrect");
+    assert_ne!(failure.output, failure.expected_output);
+
+    Ok(())
+}
+
+#[test]
+async fn should_ignore_task_constraints_for_helpers() -> GraderResult<()> {
+    dotenv().ok();
+
+    let generator = "#include <cstdio>\nint main() { std::printf(\"1 2\\n\"); }\n";
+    let result = Submission::try_from(
+        "a_plus_b_h",
+        "000048_constrained",
+        "cpp",
+        &[source::SourceFile::named("gen.cpp", generator)],
+        MessageSink,
+    )
+    .await;
+    assert!(matches!(
+        result,
+        Err(GraderError::InvalidSourceFiles { .. })
+    ));
+
+    let helper = Submission::try_helper_from_config(
+        config::get()?,
+        "a_plus_b_h",
+        "000048_helper",
+        "cpp",
+        &[source::SourceFile::named("gen.cpp", generator)],
+        MessageSink,
+    )
+    .await?;
+    assert_eq!(helper.code_path, vec![helper.tmp_path.join("gen.cpp")]);
+    assert_eq!(helper.task_files, Vec::<PathBuf>::new());
+    assert!(!helper.tmp_path.join("grader.cpp").exists());
+    assert!(!helper.tmp_path.join("a_plus_b.h").exists());
+
+    Ok(())
+}
+
+#[test]
+async fn should_read_scoring() -> GraderResult<()> {
+    dotenv().ok();
+
+    let tmp_dir = crate::utils::tests::TempDir::new("scoring");
+    let manifest_path = tmp_dir.0.join("manifest.yaml");
+    let write = |scoring: &str| {
+        std::fs::write(
+            &manifest_path,
+            format!("task_id: \"a_plus_b\"\n{scoring}groups:\n  - full_score: 100\n    tests: 1\n"),
+        )
+    };
+
+    write("scoring:\n  type: \"relative\"\n  best: 42\n  objective: \"min\"\n")?;
+    let manifest = Manifest::from(manifest_path.clone())?;
+    assert_eq!(
+        manifest.scoring,
+        Some(Scoring::Relative {
+            best: 42.0,
+            objective: Objective::Min,
+        })
+    );
+    manifest.save(&manifest_path)?;
+    assert_eq!(Manifest::from(manifest_path.clone())?, manifest);
+
+    write("scoring:\n  type: \"time\"\n  good: 0.5\n  bad: 2\n")?;
+    let manifest = Manifest::from(manifest_path.clone())?;
+    assert_eq!(
+        manifest.scoring,
+        Some(Scoring::Time {
+            good: 0.5,
+            bad: 2.0
+        })
+    );
+
+    write("scoring:\n  type: \"time\"\n  good: 2\n  bad: 1\n")?;
+    assert!(matches!(
+        Manifest::from(manifest_path.clone()),
+        Err(GraderError::InvalidTask { .. })
+    ));
+
+    Ok(())
+}
+
+#[test]
+async fn should_apply_scoring() -> GraderResult<()> {
+    let run = |status: &str, score: f64, time_usage: f64| RunResult {
+        status: String::from(status),
+        score,
+        time_usage,
+        ..Default::default()
+    };
+
+    let time = Scoring::Time {
+        good: 1.0,
+        bad: 3.0,
+    };
+    let mut result = run("Correct", 100.0, 0.5);
+    time.apply(&mut result, 1.0);
+    assert_eq!((result.status.as_str(), result.score), ("Correct", 100.0));
+
+    let mut result = run("Correct", 100.0, 2.5);
+    time.apply(&mut result, 1.0);
+    assert_eq!(
+        (result.status.as_str(), result.score),
+        ("Partially Correct", 25.0)
+    );
+
+    let mut result = run("Correct", 100.0, 5.0);
+    time.apply(&mut result, 2.0);
+    assert_eq!(
+        (result.status.as_str(), result.score),
+        ("Partially Correct", 25.0)
+    );
+
+    let mut result = run("Incorrect", 0.0, 2.0);
+    time.apply(&mut result, 1.0);
+    assert_eq!((result.status.as_str(), result.score), ("Incorrect", 0.0));
+
+    let relative = Scoring::Relative {
+        best: 80.0,
+        objective: Objective::Max,
+    };
+    let mut result = run("Correct", 60.0, 0.1);
+    relative.apply(&mut result, 1.0);
+    assert_eq!(
+        (result.status.as_str(), result.score),
+        ("Partially Correct", 75.0)
+    );
+
+    let relative = Scoring::Relative {
+        best: 80.0,
+        objective: Objective::Min,
+    };
+    let mut result = run("Correct", 50.0, 0.1);
+    relative.apply(&mut result, 1.0);
+    assert_eq!((result.status.as_str(), result.score), ("Correct", 100.0));
+
+    Ok(())
+}
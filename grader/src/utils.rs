@@ -1,59 +1,106 @@
-use crate::s;
-use std::{env, fs, path::PathBuf};
+use crate::errors::{GraderError, GraderResult};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Output,
+    time::Duration,
+};
+use tokio::{io::AsyncReadExt, process::Command};
 use yaml_rust::{Yaml, YamlLoader};
 
 use log::info;
 
-pub fn get_env(name: &'static str) -> String {
-    env::var(name).unwrap()
-}
+/// Read at most `limit` bytes of `path` as lossy UTF-8, marking truncated
+/// content with a trailing `...`. Missing files read as an empty string.
+pub async fn read_excerpt(path: &Path, limit: usize) -> String {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(_) => return String::new(),
+    };
+    let mut content = Vec::new();
+    if file
+        .take(limit as u64 + 1)
+        .read_to_end(&mut content)
+        .await
+        .is_err()
+    {
+        return String::new();
+    }
 
-pub fn get_base_path() -> PathBuf {
-    PathBuf::from(env::var("BASE_PATH").unwrap())
+    let truncated = content.len() > limit;
+    content.truncate(limit);
+    let mut excerpt = String::from_utf8_lossy(&content).into_owned();
+    if truncated {
+        excerpt.push_str("...");
+    }
+    excerpt
 }
 
-pub fn load_yaml(path: PathBuf) -> Yaml {
+/// Load the first document of a yaml file, reporting unreadable or malformed
+/// files as errors and treating an empty document as `Yaml::Null`.
+pub fn load_yaml(path: &Path) -> GraderResult<Yaml> {
     info!("finding yaml at path: {path:?}");
-    let file = fs::read_to_string(path).expect("Unable to read yaml file");
-    YamlLoader::load_from_str(&file)
-        .unwrap()
+    let file = fs::read_to_string(path)?;
+    Ok(YamlLoader::load_from_str(&file)
+        .map_err(|e| GraderError::parse_err("yaml", e))?
         .into_iter()
         .next()
-        .unwrap()
+        .unwrap_or(Yaml::Null))
 }
 
-fn yaml_unwrap_hash(yaml: Yaml, arg: &str) -> Option<Yaml> {
-    yaml.into_hash().unwrap().remove(&Yaml::String(s!(arg)))
+/// Run `command` to completion without blocking the runtime, killing it if
+/// it is still running after `timeout`.
+pub async fn output_with_timeout(command: &mut Command, timeout: Duration) -> GraderResult<Output> {
+    let child = command.kill_on_drop(true).output();
+    match tokio::time::timeout(timeout, child).await {
+        Ok(output) => Ok(output?),
+        Err(_) => Err(GraderError::timeout(format!(
+            "{command:?} did not finish within {timeout:?}"
+        ))),
+    }
 }
 
-pub fn get_code_extension(language: &str) -> String {
-    let config = load_yaml(get_base_path().join("scripts").join("config.yaml"));
+/// Check that `name` is a single, non-hidden path component made of ASCII
+/// letters, digits, `_`, `-` and `.`, so it cannot leave the directory it is
+/// joined onto.
+pub fn check_identifier(name: &str) -> GraderResult<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(GraderError::invalid_path(format!(
+            "{name:?} is not a valid name"
+        )))
+    }
+}
 
-    for lang in yaml_unwrap_hash(config, "language")
-        .unwrap()
-        .into_vec()
-        .unwrap()
-    {
-        if Some(language) == lang["id"].as_str() {
-            return yaml_unwrap_hash(lang, "extension")
-                .unwrap()
-                .into_string()
-                .unwrap();
+/// Join the identifier `name` onto `root`, failing if the result resolves
+/// outside `root`, e.g. through a symlink. Paths that do not exist yet are
+/// only checked by name.
+pub fn join_within(root: &Path, name: &str) -> GraderResult<PathBuf> {
+    check_identifier(name)?;
+    let path = root.join(name);
+    if let (Ok(root), Ok(resolved)) = (fs::canonicalize(root), fs::canonicalize(&path)) {
+        if !resolved.starts_with(&root) {
+            return Err(GraderError::invalid_path(format!(
+                "{path:?} resolves outside of {root:?}"
+            )));
         }
     }
-
-    String::new()
+    Ok(path)
 }
 
-pub fn get_message(status: &str) -> String {
-    let config = load_yaml(get_base_path().join("scripts").join("config.yaml"));
-    yaml_unwrap_hash(yaml_unwrap_hash(config, "message").unwrap(), status)
-        .map_or(String::new(), |value| value.into_string().unwrap())
+/// Quote `arg` so it is passed verbatim as a single word by bash.
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
 }
 
 #[cfg(test)]
 pub mod tests {
-    use crate::utils::get_env;
     use std::{env, fs, path::PathBuf, process::Command};
 
     pub struct TempDir(pub PathBuf);
@@ -66,32 +113,95 @@ pub mod tests {
 
     impl TempDir {
         pub fn new(tmp_name: &'static str) -> Self {
-            let tmp_path = PathBuf::from(get_env("TEMPORARY_PATH")).join(tmp_name);
+            let tmp_path = get_tmp_path().join(tmp_name);
             fs::create_dir(&tmp_path).expect("Unable to create tmp directory");
             Self(tmp_path)
         }
     }
 
     pub fn get_example_dir() -> PathBuf {
-        PathBuf::from(env::current_dir().unwrap())
+        env::current_dir()
+            .unwrap()
             .parent()
             .unwrap()
             .join("example")
     }
 
     pub fn get_tmp_path() -> PathBuf {
-        PathBuf::from(get_env("TEMPORARY_PATH"))
+        PathBuf::from(env::var("TEMPORARY_PATH").expect("TEMPORARY_PATH is not set"))
+    }
+
+    #[tokio::test]
+    async fn should_read_excerpt() {
+        dotenv::dotenv().ok();
+
+        let tmp_dir = TempDir::new("read_excerpt");
+        fs::write(tmp_dir.0.join("short"), "1 2\n").unwrap();
+        fs::write(tmp_dir.0.join("long"), "0123456789").unwrap();
+
+        assert_eq!(
+            super::read_excerpt(&tmp_dir.0.join("short"), 8).await,
+            "1 2\n"
+        );
+        assert_eq!(
+            super::read_excerpt(&tmp_dir.0.join("long"), 8).await,
+            "01234567..."
+        );
+        assert_eq!(super::read_excerpt(&tmp_dir.0.join("missing"), 8).await, "");
+    }
+
+    #[test]
+    fn should_reject_unsafe_names() {
+        dotenv::dotenv().ok();
+
+        for name in ["a_plus_b", "000123", "code_0.h", "task-1"].iter() {
+            assert!(super::check_identifier(name).is_ok(), "{}", name);
+        }
+        for name in ["", ".", "..", "../../etc", "a/b", ".hidden", "a b", "/etc"].iter() {
+            assert!(super::check_identifier(name).is_err(), "{}", name);
+        }
+
+        let tmp_dir = TempDir::new("should_reject_unsafe_names");
+        fs::create_dir(tmp_dir.0.join("inside")).unwrap();
+        std::os::unix::fs::symlink("/etc", tmp_dir.0.join("outside")).unwrap();
+        assert!(super::join_within(&tmp_dir.0, "inside").is_ok());
+        assert!(super::join_within(&tmp_dir.0, "missing").is_ok());
+        assert!(matches!(
+            super::join_within(&tmp_dir.0, "outside"),
+            Err(crate::errors::GraderError::InvalidPath { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_kill_command_after_timeout() {
+        let output = super::output_with_timeout(
+            tokio::process::Command::new("echo").arg("1"),
+            std::time::Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(output.stdout, b"1\n");
+
+        let result = super::output_with_timeout(
+            tokio::process::Command::new("sleep").arg("10"),
+            std::time::Duration::from_millis(100),
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(crate::errors::GraderError::Timeout { .. })
+        ));
     }
 
     pub fn compile_cpp(tmp_dir: &PathBuf, prog_file: &PathBuf) {
         Command::new(
-            &get_example_dir()
+            get_example_dir()
                 .join("scripts")
                 .join("compile_scripts")
                 .join("cpp"),
         )
-        .arg(&tmp_dir)
-        .arg(&prog_file)
+        .arg(tmp_dir)
+        .arg(prog_file)
         .output()
         .expect("Unable to compile file");
     }
@@ -0,0 +1,97 @@
+//! Stress test a candidate solution from the command line:
+//!
+//! ```text
+//! stress <task_id> <language>:<generator> <language>:<brute> <language>:<candidate> [iterations]
+//! ```
+//!
+//! The generator and the brute-force solution are built as helpers of the
+//! task, the candidate as a regular submission. The `StressResult` is printed
+//! as JSON. The exit code is 0 when the candidate passed every input, 1 when
+//! a failing input was found and 2 on an error.
+
+use std::{error::Error, path::Path, process};
+
+use futures::sink::drain;
+use grader::submission::{source::SourceFile, stress, Submission};
+
+const DEFAULT_ITERATIONS: u64 = 100;
+const USAGE: &str = "usage: stress <task_id> <language>:<generator> <language>:<brute> \
+    <language>:<candidate> [iterations]";
+
+/// Split a `<language>:<path>` argument and read the file it names.
+fn read_program(arg: &str) -> Result<(String, SourceFile), Box<dyn Error>> {
+    let (language, path) = arg
+        .split_once(':')
+        .ok_or_else(|| format!("{arg:?} is not <language>:<path>"))?;
+    let path = Path::new(path);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("{path:?} is not a file name"))?;
+    Ok((
+        language.to_owned(),
+        SourceFile::named(name, std::fs::read(path)?),
+    ))
+}
+
+async fn run(args: &[String]) -> Result<bool, Box<dyn Error>> {
+    let config = grader::config::get()?;
+    config.check_sandbox()?;
+
+    let task_id = &args[0];
+    let iterations = match args.get(4) {
+        Some(iterations) => iterations
+            .parse()
+            .map_err(|_| format!("{iterations:?} is not a number of iterations"))?,
+        None => DEFAULT_ITERATIONS,
+    };
+    let prefix = format!("stress_{}", process::id());
+    let (language, generator) = read_program(&args[1])?;
+    let mut generator = Submission::try_helper_from_config(
+        config.clone(),
+        task_id,
+        format!("{prefix}_generator"),
+        language,
+        &[generator],
+        drain(),
+    )
+    .await?;
+    let (language, brute) = read_program(&args[2])?;
+    let mut brute = Submission::try_helper_from_config(
+        config.clone(),
+        task_id,
+        format!("{prefix}_brute"),
+        language,
+        &[brute],
+        drain(),
+    )
+    .await?;
+    let (language, candidate) = read_program(&args[3])?;
+    let mut candidate =
+        Submission::try_from_config(config, task_id, prefix, language, &[candidate], drain())
+            .await?;
+
+    let result = stress::stress(&mut generator, &mut brute, &mut candidate, iterations).await?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(result.failure.is_none())
+}
+
+#[tokio::main]
+async fn main() {
+    pretty_env_logger::init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if !(4..=5).contains(&args.len()) {
+        eprintln!("{USAGE}");
+        process::exit(2);
+    }
+
+    match run(&args).await {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            eprintln!("stress test failed: {e}");
+            process::exit(2);
+        }
+    }
+}
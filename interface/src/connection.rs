@@ -5,9 +5,13 @@ use lapin::{options::BasicPublishOptions, BasicProperties, Channel};
 use openssl::ssl::{SslConnector, SslMethod};
 use postgres_openssl::{MakeTlsConnector, TlsStream};
 use tokio::sync::Mutex;
-use tokio_postgres::{Client, Connection, Socket};
+use tokio_postgres::{Client, Connection, Row, Socket};
 
-use crate::{cfg::{RabbitMqConfig, DatabaseConfig}, error::Error, runner::JudgeState};
+use crate::{
+    cfg::{DatabaseConfig, RabbitMqConfig},
+    error::Error,
+    runner::JudgeState,
+};
 use log::*;
 
 const EPS: f64 = 1e-6;
@@ -38,9 +42,14 @@ impl SharedClient {
         &self,
         submission_id: &str,
         state: &Mutex<JudgeState>,
-        group: GroupResult,
+        mut group: GroupResult,
     ) -> Result<(), Error> {
         debug!("received new group result for {submission_id}");
+        for run_result in group.run_result.iter_mut() {
+            if !run_result.sample {
+                run_result.stderr.clear();
+            }
+        }
         let new_score = group.score;
         let new_time = group
             .run_result
@@ -87,35 +96,7 @@ impl SharedClient {
             )
             .await?;
 
-        let id: i32 = row.get(0);
-        let groups: serde_json::Value = row.get(1);
-        let status: String = row.get(2);
-        let score: i32 = row.get(3);
-
-        let payload = serde_json::json!({
-            "id": id,
-            "groups": groups,
-            "status": status,
-            "score": score,
-        })
-        .to_string();
-
-        let payload = payload.as_bytes();
-
-        if let Err(e) = self
-            .rmq_channel
-            .basic_publish(
-                "",
-                &self.update_routing_key,
-                BasicPublishOptions::default(),
-                payload,
-                BasicProperties::default(),
-            )
-            .await
-        {
-            log::error!("Unable to publish message: {e}");
-        }
-
+        self.publish_update(&row, None).await;
         Ok(())
     }
 
@@ -128,20 +109,52 @@ impl SharedClient {
             )
             .await?;
 
+        self.publish_update(&row, None).await;
+        Ok(())
+    }
+
+    pub async fn update_compilation_error(
+        &self,
+        submission_id: &str,
+        msg: String,
+        compile_message: String,
+    ) -> Result<(), Error> {
+        debug!("change {submission_id}'s status to {msg} with compiler message");
+        let row = self
+            .db_client
+            .query_one(
+                "UPDATE submission SET status = $1, compile_message = $2 WHERE id = $3 \
+                            RETURNING id, groups, status, score, compile_message",
+                &[
+                    &msg,
+                    &compile_message,
+                    &submission_id.parse::<i32>().unwrap(),
+                ],
+            )
+            .await?;
+
+        self.publish_update(&row, Some(row.get(4))).await;
+        Ok(())
+    }
+
+    /// Publish the state of a submission from a row returning its `id`,
+    /// `groups`, `status` and `score`.
+    async fn publish_update(&self, row: &Row, compile_message: Option<String>) {
         let id: i32 = row.get(0);
         let groups: serde_json::Value = row.get(1);
         let status: String = row.get(2);
         let score: i32 = row.get(3);
 
-        let payload = serde_json::json!({
+        let mut payload = serde_json::json!({
             "id": id,
             "groups": groups,
             "status": status,
             "score": score,
-        })
-        .to_string();
-
-        let payload = payload.as_bytes();
+        });
+        if let Some(compile_message) = compile_message {
+            payload["compile_message"] = serde_json::Value::String(compile_message);
+        }
+        let payload = payload.to_string();
 
         if let Err(e) = self
             .rmq_channel
@@ -149,19 +162,19 @@ impl SharedClient {
                 "",
                 &self.update_routing_key,
                 BasicPublishOptions::default(),
-                payload,
+                payload.as_bytes(),
                 BasicProperties::default(),
             )
             .await
         {
             log::error!("Unable to publish message: {e}");
         }
-
-        Ok(())
     }
 }
 
-pub async fn connect_db(db_config: &DatabaseConfig) -> (Client, Connection<Socket, TlsStream<Socket>>) {
+pub async fn connect_db(
+    db_config: &DatabaseConfig,
+) -> (Client, Connection<Socket, TlsStream<Socket>>) {
     let addr = format!(
         "postgres://{username}:{password}@{host}:{port}/{dbname}",
         username = db_config.username,
@@ -2,12 +2,17 @@ use grader::submission::SubmissionStatus;
 
 pub static PULL_MSG: &str = "In Queue";
 pub static ERROR_MSG: &str = "Judge Error";
+pub static INPUT_LIMIT_MSG: &str = "Input Limit Exceeded";
 
 pub fn parse_submission_status(status: SubmissionStatus) -> String {
     match status {
+        SubmissionStatus::LanguageNotSupported => "Language Not Supported".to_string(),
+        SubmissionStatus::SourceLimitExceeded => "Source Limit Exceeded".to_string(),
+        SubmissionStatus::InvalidSourceFiles => "Invalid Source Files".to_string(),
         SubmissionStatus::Compiling => "Compiling".to_string(),
         SubmissionStatus::Compiled => "Compiled".to_string(),
         SubmissionStatus::CompilationError(_) => "Compilation Error".to_string(),
+        SubmissionStatus::CompilationTimeout => "Compilation Timeout".to_string(),
         SubmissionStatus::Running(idx) => format!("Running on test #{}", idx),
         SubmissionStatus::Done(_) => "Completed".to_string(),
         _ => ERROR_MSG.to_string(),
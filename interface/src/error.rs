@@ -1,5 +1,6 @@
 use grader::errors::GraderError;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("submission not found")]
@@ -18,6 +19,8 @@ pub enum Error {
     InvalidSubmissionId(String),
     #[error("invalid code, expectd array")]
     InvalidCode,
+    #[error("rabbitmq error: {0}")]
+    RmqError(#[from] lapin::Error),
     #[error("grader error: {0}")]
     GraderError(#[from] GraderError),
 }
@@ -1,7 +1,8 @@
+use base64::Engine;
 use cfg::DatabaseConfig;
 use futures::{Sink, Stream, StreamExt};
 use serde_json::Value;
-use std::{io::Cursor, sync::Arc};
+use std::{io::Cursor, sync::Arc, time::Duration};
 use tokio::task::JoinHandle;
 
 mod cfg;
@@ -10,12 +11,20 @@ mod constants;
 mod error;
 mod rmq;
 mod runner;
+mod test_run;
 
 use connection::SharedClient;
 use error::Error;
+use grader::{
+    errors::GraderError,
+    submission::{source::SourceFile, SubmissionStatus},
+};
 
 type SubmissionId = String;
 
+/// How often the grader's `config.yaml` is checked for changes.
+const GRADER_CONFIG_RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+
 async fn pull_and_judge(id: SubmissionId, client: SharedClient) -> Result<(), Error> {
     log::debug!("start judging {id}");
 
@@ -30,7 +39,7 @@ async fn pull_and_judge(id: SubmissionId, client: SharedClient) -> Result<(), Er
         .db_client
         .query(
             "SELECT task_id, language, \
-            code, status  FROM submission WHERE id = $1",
+            code, status, locale FROM submission WHERE id = $1",
             &[&lookup_id_as_query_args],
         )
         .await?;
@@ -43,6 +52,7 @@ async fn pull_and_judge(id: SubmissionId, client: SharedClient) -> Result<(), Er
     let language: String = rows[0].get(1);
     let code: Vec<u8> = rows[0].get(2);
     let status: String = rows[0].get(3);
+    let locale: Option<String> = rows[0].get(4);
 
     if status != constants::PULL_MSG {
         return Err(Error::AlreadyJudge);
@@ -56,8 +66,8 @@ async fn pull_and_judge(id: SubmissionId, client: SharedClient) -> Result<(), Er
         .as_array()
         .ok_or(Error::InvalidCode)?
         .iter()
-        .map(|x| x.as_str().ok_or(Error::InvalidCode).map(|x| x.to_string()))
-        .collect::<Result<Vec<String>, Error>>()?;
+        .map(parse_source_file)
+        .collect::<Result<Vec<SourceFile>, Error>>()?;
 
     let val: i32 = 0;
     let empty_data = serde_json::to_value(Vec::new() as Vec<i32>)?;
@@ -79,15 +89,12 @@ async fn pull_and_judge(id: SubmissionId, client: SharedClient) -> Result<(), Er
         .await?;
 
     log::debug!("start judging submission {lookup_id}");
-    let result = runner::judge(task_id, &lookup_id, language, &code, client.clone()).await;
+    let result = runner::judge(task_id, &lookup_id, language, locale, &code, client.clone()).await;
     match result {
         Ok(_) => Ok(()),
         Err(e) => {
-            if (client
-                .update_status(&lookup_id, constants::ERROR_MSG.to_string())
-                .await)
-                .is_err()
-            {
+            let status = error_status(&e);
+            if (client.update_status(&lookup_id, status).await).is_err() {
                 log::warn!("failed to update status to server");
             }
             Err(Error::GraderError(e))
@@ -95,6 +102,55 @@ async fn pull_and_judge(id: SubmissionId, client: SharedClient) -> Result<(), Er
     }
 }
 
+/// The status reported for a submission the grader failed on.
+fn error_status(e: &GraderError) -> String {
+    match e {
+        GraderError::LanguageNotSupported { .. } => {
+            constants::parse_submission_status(SubmissionStatus::LanguageNotSupported)
+        }
+        GraderError::SourceLimitExceeded { .. } => {
+            constants::parse_submission_status(SubmissionStatus::SourceLimitExceeded)
+        }
+        GraderError::InvalidSourceFiles { .. } => {
+            constants::parse_submission_status(SubmissionStatus::InvalidSourceFiles)
+        }
+        GraderError::InputLimitExceeded { .. } => constants::INPUT_LIMIT_MSG.to_string(),
+        _ => constants::ERROR_MSG.to_string(),
+    }
+}
+
+/// A submitted file is either its content as a string or an object with its
+/// `content`, and optionally its `name`, `"encoding": "base64"` for binary
+/// content, and `archive` (`zip`, `tar` or `tar.zst`) to unpack it.
+fn parse_source_file(value: &Value) -> Result<SourceFile, Error> {
+    let file = match value {
+        Value::String(content) => return Ok(SourceFile::from(content.clone())),
+        Value::Object(file) => file,
+        _ => return Err(Error::InvalidCode),
+    };
+    let string = |key: &str| {
+        file.get(key)
+            .map(|value| value.as_str().ok_or(Error::InvalidCode))
+            .transpose()
+    };
+
+    let content = string("content")?.ok_or(Error::InvalidCode)?;
+    let content = match string("encoding")? {
+        None => content.as_bytes().to_vec(),
+        Some("base64") => base64::engine::general_purpose::STANDARD
+            .decode(content)
+            .map_err(|_| Error::InvalidCode)?,
+        Some(_) => return Err(Error::InvalidCode),
+    };
+    Ok(SourceFile {
+        name: string("name")?.map(|name| name.to_string()),
+        content,
+        archive: string("archive")?
+            .map(|kind| kind.parse().map_err(|_| Error::InvalidCode))
+            .transpose()?,
+    })
+}
+
 #[tokio::main]
 async fn main() {
     pretty_env_logger::init();
@@ -107,6 +163,19 @@ async fn main() {
         }
     };
 
+    let grader_config = match grader::config::get() {
+        Ok(x) => x,
+        Err(e) => {
+            log::error!("Unable to load grader config: {e}");
+            return;
+        }
+    };
+    if let Err(e) = grader_config.check_sandbox() {
+        log::error!("Unable to use sandbox: {e}");
+        return;
+    }
+    grader::config::watch(GRADER_CONFIG_RELOAD_INTERVAL);
+
     log::info!("starting...");
 
     let (tx, rx) = futures::channel::mpsc::unbounded::<SubmissionId>();
@@ -120,7 +189,7 @@ async fn main() {
             .expect("Unable to create rabbitmq channel"),
     );
 
-    let shared_client = SharedClient::new(client, rmq_channel, &config.rmq_config);
+    let shared_client = SharedClient::new(client, rmq_channel.clone(), &config.rmq_config);
 
     let db_connection_handler = tokio::spawn(async move {
         if let Err(e) = connection.await {
@@ -135,6 +204,14 @@ async fn main() {
     let submission_handler = handle_message(shared_client.clone(), rx);
     log::info!("start listening for submission through channel");
 
+    let rmq_config = config.rmq_config.clone();
+    let test_run_handler = tokio::spawn(async move {
+        if let Err(e) = test_run::listen_test_run(rmq_channel, &rmq_config).await {
+            log::error!("test run error: {e}");
+        }
+    });
+    log::info!("start listening for test run requests");
+
     tokio::select! {
         _ = submission_handler => {
             log::warn!("submission handler died, exiting...");
@@ -144,6 +221,10 @@ async fn main() {
             log::warn!("db notification handler died, exiting...");
             std::process::exit(1);
         },
+        _ = test_run_handler => {
+            log::warn!("test run handler died, exiting...");
+            std::process::exit(1);
+        },
         _ = db_connection_handler => {
             log::warn!("db connection handler died, exiting...");
             std::process::exit(1);
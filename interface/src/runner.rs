@@ -11,6 +11,7 @@ use grader::{
     errors::GraderResult,
     submission::{
         result::{GroupResult, SubmissionResult},
+        source::SourceFile,
         Submission, SubmissionMessage, SubmissionStatus,
     },
 };
@@ -33,7 +34,8 @@ pub async fn judge(
     task_id: impl ToString,
     submission_id: impl ToString,
     language: impl ToString,
-    code: &[String],
+    locale: Option<String>,
+    code: &[SourceFile],
     client: SharedClient,
 ) -> GraderResult<SubmissionResult> {
     let task_id = task_id.to_string();
@@ -54,8 +56,9 @@ pub async fn judge(
 
     let (tx, rx) = futures::channel::mpsc::unbounded::<SubmissionMessage>();
 
-    let mut submission =
-        Submission::try_from(task_id, submission_id.clone(), language, code, tx).await?;
+    let mut submission = Submission::try_from(task_id, submission_id.clone(), language, code, tx)
+        .await?
+        .with_locale(locale);
 
     tokio::spawn(handle_update_message(
         client.clone(),
@@ -150,6 +153,19 @@ async fn handle_update_message<T>(
                 }
                 break;
             }
+            SubmissionMessage::Status(SubmissionStatus::CompilationError(compile_message)) => {
+                let status = SubmissionStatus::CompilationError(String::new());
+                if let Err(e) = client
+                    .update_compilation_error(
+                        &submission_id,
+                        parse_submission_status(status),
+                        compile_message,
+                    )
+                    .await
+                {
+                    warn!("unable to update status to database: {e}");
+                }
+            }
             SubmissionMessage::Status(status) => {
                 if let Err(e) = client
                     .update_status(&submission_id, parse_submission_status(status))
@@ -158,6 +174,12 @@ async fn handle_update_message<T>(
                     warn!("unable to update status to database: {e}");
                 }
             }
+            SubmissionMessage::RunResult(run_result) if !run_result.stderr.is_empty() => {
+                debug!(
+                    "stderr of {submission_id} on test #{}:\n{}",
+                    run_result.test_index, run_result.stderr
+                );
+            }
             SubmissionMessage::GroupResult(group_result) => {
                 log::info!("Group result");
                 if let Err(e) = client
@@ -0,0 +1,150 @@
+//! Custom-input test runs requested through RabbitMQ.
+//!
+//! A request on `test_run.request.{env}` is a JSON object with the `id` of the
+//! request, `task_id`, `language`, `code` in the same format as a submission,
+//! and the `input` to run on. The outcome is published to
+//! `test_run.result.{env}` as the grader's test run result with the request's
+//! `id`.
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+use grader::submission::{result::TestRunResult, source::SourceFile, Submission};
+use lapin::{
+    options::{
+        BasicAckOptions, BasicConsumeOptions, BasicPublishOptions, BasicQosOptions,
+        QueueDeclareOptions,
+    },
+    types::FieldTable,
+    BasicProperties, Channel,
+};
+use log::{debug, warn};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{cfg::RabbitMqConfig, constants, error::Error, error_status, parse_source_file};
+
+/// Test runs handled at once; further requests wait in the queue until one is
+/// acknowledged.
+const TEST_RUN_PREFETCH: u16 = 8;
+
+#[derive(Debug, Deserialize)]
+struct TestRunRequest {
+    id: Value,
+    task_id: String,
+    language: String,
+    code: Vec<Value>,
+    #[serde(default)]
+    input: String,
+}
+
+pub async fn listen_test_run(
+    channel: Arc<Channel>,
+    rmq_config: &RabbitMqConfig,
+) -> Result<(), Error> {
+    let request_queue = format!("test_run.request.{}", rmq_config.env);
+    let result_routing_key = format!("test_run.result.{}", rmq_config.env);
+
+    channel
+        .queue_declare(
+            &request_queue,
+            QueueDeclareOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+    channel
+        .basic_qos(TEST_RUN_PREFETCH, BasicQosOptions::default())
+        .await?;
+    let mut consumer = channel
+        .basic_consume(
+            &request_queue,
+            "",
+            BasicConsumeOptions::default(),
+            FieldTable::default(),
+        )
+        .await?;
+
+    while let Some(delivery) = consumer.next().await {
+        let delivery = delivery?;
+        let channel = channel.clone();
+        let result_routing_key = result_routing_key.clone();
+        tokio::spawn(async move {
+            let payload = match serde_json::from_slice::<TestRunRequest>(&delivery.data) {
+                Ok(request) => test_run(request).await,
+                Err(e) => {
+                    warn!("invalid test run request: {e}");
+                    None
+                }
+            };
+            if let Some(payload) = payload {
+                if let Err(e) = channel
+                    .basic_publish(
+                        "",
+                        &result_routing_key,
+                        BasicPublishOptions::default(),
+                        payload.to_string().as_bytes(),
+                        BasicProperties::default(),
+                    )
+                    .await
+                {
+                    log::error!("Unable to publish message: {e}");
+                }
+            }
+            if let Err(e) = delivery.ack(BasicAckOptions::default()).await {
+                warn!("failed to acknowledge test run request: {e}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Run the request and return the message to publish.
+async fn test_run(request: TestRunRequest) -> Option<Value> {
+    let id = match &request.id {
+        Value::String(id) => id.clone(),
+        id => id.to_string(),
+    };
+    debug!("start test run {id}");
+
+    let submission_id = format!("test_run_{id}");
+    let result = match run_request(&request, &submission_id).await {
+        Ok(result) => result,
+        Err(e) => {
+            warn!("failed to test run '{id}'\nreason: {e:?}");
+            let status = match e {
+                Error::GraderError(e) => error_status(&e),
+                _ => constants::ERROR_MSG.to_string(),
+            };
+            TestRunResult {
+                submission_id,
+                status,
+                ..Default::default()
+            }
+        }
+    };
+
+    let mut payload = serde_json::to_value(result).ok()?;
+    payload["id"] = request.id;
+    Some(payload)
+}
+
+async fn run_request(
+    request: &TestRunRequest,
+    submission_id: &str,
+) -> Result<TestRunResult, Error> {
+    let code = request
+        .code
+        .iter()
+        .map(parse_source_file)
+        .collect::<Result<Vec<SourceFile>, Error>>()?;
+    let mut submission = Submission::try_from(
+        &request.task_id,
+        submission_id,
+        &request.language,
+        &code,
+        futures::sink::drain(),
+    )
+    .await?;
+    Ok(submission.test_run(request.input.as_bytes()).await?)
+}